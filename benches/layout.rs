@@ -0,0 +1,47 @@
+//! `compute_layout` over a generated source of many structs referring to each other, a baseline
+//! for changes to parsing and sizing.
+
+use std::{fmt::Write, hint::black_box};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use struct_storage_layout::compute_layout;
+
+const STRUCTS: usize = 500;
+
+/// `count` structs of value types, arrays and mappings, each embedding the one at half its
+/// index and referring to others through mappings and dynamic arrays.
+fn source(count: usize) -> String {
+    let mut src = String::from("enum Side { Buy, Sell }\nuint256 constant N = 3;\n");
+    for i in 0..count {
+        writeln!(src, "struct S{i} {{").unwrap();
+        writeln!(src, "    uint{} a;", (i % 32 + 1) * 8).unwrap();
+        writeln!(src, "    bool flag;").unwrap();
+        writeln!(src, "    Side side;").unwrap();
+        writeln!(src, "    address[N * 2] owners;").unwrap();
+        writeln!(
+            src,
+            "    mapping(address => mapping(uint256 => S{})) nested;",
+            (i * 7 + 1) % count
+        )
+        .unwrap();
+        writeln!(src, "    S{}[] list;", (i * 13 + 5) % count).unwrap();
+        writeln!(src, "    bytes4[9] selectors;").unwrap();
+        if i > 0 {
+            writeln!(src, "    S{} inner;", i / 2).unwrap();
+        }
+        writeln!(src, "    string name;").unwrap();
+        writeln!(src, "}}").unwrap();
+    }
+    src
+}
+
+fn compute(c: &mut Criterion) {
+    let src = source(STRUCTS);
+    assert_eq!(compute_layout(&src).unwrap().len(), STRUCTS);
+    c.bench_function("compute_layout of 500 structs", |b| {
+        b.iter(|| compute_layout(black_box(&src)).unwrap())
+    });
+}
+
+criterion_group!(benches, compute);
+criterion_main!(benches);
@@ -0,0 +1,224 @@
+//! Reads struct definitions from solc's compact AST (`solc --ast-compact-json`) instead of
+//! parsing the Solidity source text.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::{LayoutError, SolStruct, SolType};
+
+/// Builds the struct map out of every `StructDefinition` in the AST json in `src`.
+///
+/// `src` can hold several json documents, like the per file output of `solc --ast-compact-json`
+/// with its `======= file.sol =======` headers in between.
+///
+/// Structs are named by their `canonicalName`, qualified by the contract they are declared in
+/// like `Vault.Data`, the same as [`parse_structs`](crate::parse_structs) names them.
+///
+/// Enums always take a single byte of storage (solc caps them at 256 members) so they are
+/// lowered to `uint8`, contracts to `address` and user defined value types to their underlying
+/// type.
+pub fn parse_ast(src: &str) -> Result<BTreeMap<String, SolStruct>, LayoutError> {
+    let documents = documents(src)?;
+
+    let mut declarations = HashMap::new();
+    for document in &documents {
+        collect_declarations(document, &mut declarations);
+    }
+
+    let mut structs = BTreeMap::new();
+    for node in declarations.values() {
+        if node["nodeType"] != "StructDefinition" {
+            continue;
+        }
+        let st = parse_struct(node, &declarations)?;
+        // the same name at the top level of two files
+        if structs.contains_key(&st.name) {
+            return Err(invalid(format!("struct {} is declared twice", st.name)));
+        }
+        structs.insert(st.name.clone(), st);
+    }
+
+    Ok(structs)
+}
+
+/// The `canonicalName` of a struct definition, its bare `name` in ASTs of solc versions that
+/// don't have it.
+fn struct_name(node: &Value) -> Result<&str, LayoutError> {
+    node["canonicalName"]
+        .as_str()
+        .or_else(|| node["name"].as_str())
+        .ok_or_else(|| invalid("struct definition without a name"))
+}
+
+/// A [`LayoutError::InvalidAst`] saying `msg`.
+fn invalid(msg: impl Into<String>) -> LayoutError {
+    LayoutError::InvalidAst(msg.into())
+}
+
+fn documents(src: &str) -> Result<Vec<Value>, LayoutError> {
+    let mut documents = vec![];
+
+    let mut rest = src;
+    while let Some(start) = rest.find('{') {
+        let mut stream = serde_json::Deserializer::from_str(&rest[start..]).into_iter::<Value>();
+        let document = stream
+            .next()
+            .ok_or_else(|| invalid("expected an ast json document"))?
+            .map_err(|e| invalid(format!("invalid json: {e}")))?;
+        documents.push(document);
+        rest = &rest[start + stream.byte_offset()..];
+    }
+
+    if documents.is_empty() {
+        return Err(invalid("no ast json found"));
+    }
+
+    Ok(documents)
+}
+
+/// Indexes every ast node that has an `id` so type names can be resolved through their
+/// `referencedDeclaration`.
+fn collect_declarations<'a>(value: &'a Value, declarations: &mut HashMap<i64, &'a Value>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(id) = object.get("id").and_then(Value::as_i64)
+                && object.contains_key("nodeType")
+            {
+                declarations.insert(id, value);
+            }
+
+            for value in object.values() {
+                collect_declarations(value, declarations);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_declarations(value, declarations);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_struct(
+    node: &Value,
+    declarations: &HashMap<i64, &Value>,
+) -> Result<SolStruct, LayoutError> {
+    let name = struct_name(node)?;
+
+    let mut fields = vec![];
+    for member in node["members"]
+        .as_array()
+        .ok_or_else(|| invalid(format!("struct {name} has no members")))?
+    {
+        let field = member["name"]
+            .as_str()
+            .ok_or_else(|| invalid(format!("struct {name} has a member without a name")))?;
+        let typ =
+            parse_type(&member["typeName"], declarations).map_err(|e| LayoutError::Field {
+                owner: name.to_string(),
+                field: field.to_string(),
+                source: Box::new(e),
+            })?;
+
+        let doc = member["documentation"]["text"].as_str().map(str::to_string);
+
+        fields.push((field.to_string(), typ, doc));
+    }
+
+    Ok(SolStruct {
+        name: name.to_string(),
+        fields,
+        _inner: node.to_string(),
+    })
+}
+
+fn parse_type(
+    type_name: &Value,
+    declarations: &HashMap<i64, &Value>,
+) -> Result<SolType, LayoutError> {
+    Ok(match type_name["nodeType"].as_str() {
+        Some("ElementaryTypeName") => {
+            let name = type_name["name"]
+                .as_str()
+                .ok_or_else(|| invalid("elementary type without a name"))?;
+            match name {
+                // same storage layout as `bytes`
+                "string" => SolType::String,
+                name => name.parse()?,
+            }
+        }
+        Some("UserDefinedTypeName") => {
+            let declaration = type_name["referencedDeclaration"]
+                .as_i64()
+                .and_then(|id| declarations.get(&id));
+            match declaration {
+                Some(declaration) => match declaration["nodeType"].as_str() {
+                    Some("StructDefinition") => {
+                        SolType::Custom2(struct_name(declaration)?.to_string())
+                    }
+                    Some("EnumDefinition") => SolType::Uint(8),
+                    Some("ContractDefinition") => SolType::Address,
+                    Some("UserDefinedValueTypeDefinition") => {
+                        parse_type(&declaration["underlyingType"], declarations)?
+                    }
+                    kind => {
+                        return Err(invalid(format!("unsupported user defined type: {kind:?}")));
+                    }
+                },
+                // declared in a source unit that isn't part of the input
+                None => {
+                    let type_string = type_string(type_name)?;
+                    match type_string.split_whitespace().collect::<Vec<_>>()[..] {
+                        ["struct", name, ..] => SolType::Custom2(name.to_string()),
+                        ["enum", ..] => SolType::Uint(8),
+                        ["contract", ..] => SolType::Address,
+                        _ => {
+                            return Err(invalid(format!(
+                                "unresolved user defined type: {type_string}"
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        Some("Mapping") => SolType::Mapping(
+            Box::new(parse_type(&type_name["keyType"], declarations)?),
+            Box::new(parse_type(&type_name["valueType"], declarations)?),
+        ),
+        Some("ArrayTypeName") => {
+            let base = Box::new(parse_type(&type_name["baseType"], declarations)?);
+            match &type_name["length"] {
+                Value::Null => SolType::Array(base),
+                length => {
+                    // constant expressions are only evaluated in the type string
+                    let len = match length["value"].as_str() {
+                        Some(value) => value,
+                        None => {
+                            type_string(type_name)?
+                                .split_whitespace()
+                                .next()
+                                .and_then(|s| s.strip_suffix(']'))
+                                .and_then(|s| s.rsplit_once('['))
+                                .ok_or_else(|| invalid(format!("unknown array length: {length}")))?
+                                .1
+                        }
+                    };
+                    let len = len
+                        .parse::<u64>()
+                        .map_err(|e| invalid(format!("error parsing array length {len}: {e}")))?;
+
+                    SolType::FixedArray(base, len)
+                }
+            }
+        }
+        kind => return Err(invalid(format!("unsupported type name: {kind:?}"))),
+    })
+}
+
+fn type_string(type_name: &Value) -> Result<&str, LayoutError> {
+    type_name["typeDescriptions"]["typeString"]
+        .as_str()
+        .ok_or_else(|| invalid("type name without a type string"))
+}
@@ -0,0 +1,261 @@
+//! Lays out the state variables of contracts, the same way the fields of a struct are laid out
+//! starting at slot 0.
+
+use std::collections::BTreeMap;
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{
+    DEFAULT_MAX_DEPTH, LayoutError, SolStruct, SolType, constants, lower_aliases, qualify,
+    struct_names, type_aliases,
+};
+
+static CONTRACT_HEADER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:abstract\s+)?contract\s+(?<name>\w+)[^{;]*\{")
+        .expect("valid contract header regex")
+});
+
+/// Members of a contract body that don't take up any storage.
+const NON_STORAGE_MEMBERS: &[&str] = &[
+    "function",
+    "modifier",
+    "constructor",
+    "fallback",
+    "receive",
+    "event",
+    "error",
+    "using",
+    "struct",
+    "enum",
+    "type",
+];
+
+/// Keywords that can sit between the type and the name of a state variable.
+const STATE_VARIABLE_KEYWORDS: &[&str] = &["public", "private", "internal", "override"];
+
+/// Blanks out comments, keeping string literals and line breaks as they are.
+pub(crate) fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(s) = chars.next() {
+                    out.push(s);
+                    if s == '\\' {
+                        out.extend(chars.next());
+                    } else if s == c {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Splits the body of a contract, starting right after its opening brace, into its members.
+///
+/// A member ends at a `;` or at the brace closing its own body, so functions, modifiers and
+/// nested structs are consumed whole. Returns the members with the offset into `body` they
+/// start at, along with the number of bytes of `body` up to and including the closing brace of
+/// the contract, `None` if it's never closed.
+fn split_members(body: &str) -> Option<(Vec<(usize, &str)>, usize)> {
+    let mut members = vec![];
+    let mut start = 0;
+    let mut braces = 0usize;
+    let mut parens = 0usize;
+    let mut in_string = None;
+    let mut escaped = false;
+
+    for (i, c) in body.char_indices() {
+        if let Some(quote) = in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                in_string = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => in_string = Some(c),
+            '(' => parens += 1,
+            ')' => parens = parens.saturating_sub(1),
+            '{' => braces += 1,
+            '}' if braces == 0 => {
+                members.push((start, &body[start..i]));
+                return Some((members, i + 1));
+            }
+            '}' => {
+                braces -= 1;
+                // braces inside of parens are struct literals in an initializer
+                if braces == 0 && parens == 0 {
+                    members.push((start, &body[start..=i]));
+                    start = i + 1;
+                }
+            }
+            ';' if braces == 0 && parens == 0 => {
+                members.push((start, &body[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// A state variable, by name and type.
+struct StateVariable {
+    name: String,
+    typ: String,
+    /// Kept in transient storage, which is laid out on its own from slot 0.
+    transient: bool,
+}
+
+/// Turns a state variable declaration on `line` into its name and type, `None` for constants
+/// and immutables as they aren't kept in storage.
+fn parse_state_variable(member: &str, line: usize) -> Result<Option<StateVariable>, LayoutError> {
+    // drop the initializer, minding the `=>` of mappings
+    let declaration = member
+        .char_indices()
+        .find(|&(i, c)| c == '=' && !member[i + 1..].starts_with('>'))
+        .map_or(member, |(i, _)| &member[..i]);
+
+    let mut tokens = declaration.split_whitespace().collect::<Vec<_>>();
+    if tokens
+        .iter()
+        .any(|token| matches!(*token, "constant" | "immutable"))
+    {
+        return Ok(None);
+    }
+    let transient = tokens.contains(&"transient");
+    tokens.retain(|token| {
+        !STATE_VARIABLE_KEYWORDS.contains(token)
+            && *token != "transient"
+            && !token.starts_with("override(")
+    });
+
+    match tokens[..] {
+        [.., _, name] => Ok(Some(StateVariable {
+            name: name.to_string(),
+            typ: tokens[..tokens.len() - 1].join(" "),
+            transient,
+        })),
+        _ => Err(LayoutError::ParseError {
+            line,
+            msg: format!("invalid state variable: {}", member.trim()),
+        }),
+    }
+}
+
+/// Collects the state variables of every contract defined in `src`, as a struct named after the
+/// contract with them as its fields.
+///
+/// Functions, modifiers, events and the other members that aren't storage are skipped, along
+/// with their bodies. Inherited state variables aren't included, and enums are lowered to
+/// `uint8`.
+///
+/// `transient` state variables live in a storage of their own, they are collected into a
+/// separate `Name (transient)` struct, numbered from slot 0 as well.
+pub fn parse_contracts(src: &str) -> Result<BTreeMap<String, SolStruct>, LayoutError> {
+    let src = strip_comments(src);
+    // comments are blanked out keeping their line breaks, so lines still count the same
+    let line_at = |offset: usize| src[..offset].matches('\n').count() + 1;
+    let aliases = type_aliases(&src);
+    let constants = constants(&src);
+    let structs = struct_names(&src);
+
+    let mut contracts = BTreeMap::new();
+    let mut offset = 0;
+    while let Some(captures) = CONTRACT_HEADER_REGEX.captures(&src[offset..]) {
+        let name = captures["name"].to_string();
+        let header = captures
+            .get(0)
+            .map_or(0..0, |m| offset + m.start()..offset + m.end());
+        let body = &src[header.end..];
+        let (members, len) = split_members(body).ok_or_else(|| LayoutError::ParseError {
+            line: line_at(header.start),
+            msg: format!("unterminated body of contract {name}"),
+        })?;
+
+        let mut fields = vec![];
+        let mut transient_fields = vec![];
+        for (start, member) in members {
+            let Some(keyword) = member.split_whitespace().next() else {
+                continue;
+            };
+            if NON_STORAGE_MEMBERS
+                .iter()
+                .any(|skipped| keyword.split('(').next() == Some(*skipped))
+            {
+                continue;
+            }
+
+            let line = line_at(header.end + start + (member.len() - member.trim_start().len()));
+            if let Some(var) = parse_state_variable(member, line)? {
+                let in_field = |e| LayoutError::Field {
+                    owner: name.clone(),
+                    field: var.name.clone(),
+                    source: Box::new(e),
+                };
+                let typ = SolType::parse_with_constants(&var.typ, &constants).map_err(in_field)?;
+                let typ = qualify(typ, Some(&name), &structs, DEFAULT_MAX_DEPTH)
+                    .and_then(|typ| lower_aliases(typ, &aliases, DEFAULT_MAX_DEPTH))
+                    .map_err(in_field)?;
+                let field = (var.name, typ, None);
+                if var.transient {
+                    transient_fields.push(field);
+                } else {
+                    fields.push(field);
+                }
+            }
+        }
+
+        if !transient_fields.is_empty() {
+            let name = format!("{name} (transient)");
+            contracts.insert(
+                name.clone(),
+                SolStruct {
+                    name,
+                    fields: transient_fields,
+                    _inner: body[..len].to_string(),
+                },
+            );
+        }
+        contracts.insert(
+            name.clone(),
+            SolStruct {
+                name,
+                fields,
+                _inner: body[..len].to_string(),
+            },
+        );
+        offset = header.end + len;
+    }
+
+    Ok(contracts)
+}
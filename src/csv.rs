@@ -0,0 +1,40 @@
+//! Comma separated rows of the computed layouts, for spreadsheets and scripts.
+
+use std::fmt::Write as _;
+
+use crate::LayoutReport;
+
+/// The header row of [`LayoutReport::csv`].
+pub const CSV_HEADER: &str = "struct,slot,offset,bits,type,name";
+
+/// Quotes `text` if it holds a comma, a quote or a line break.
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+impl LayoutReport {
+    /// One row per field with the struct name, its slot, byte offset, bits, type and name, in
+    /// the columns of [`CSV_HEADER`]. The header itself is left out so the rows of several
+    /// layouts can go under a single one.
+    pub fn csv(&self) -> String {
+        let mut out = String::new();
+        for location in &self.fields {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                field(&self.struct_name),
+                location.slot,
+                location.offset,
+                location.bits,
+                field(&location.typ.to_string()),
+                field(&location.name)
+            );
+        }
+
+        out
+    }
+}
@@ -0,0 +1,80 @@
+//! ASCII diagram of how the fields of a struct are packed into its slots.
+
+use std::fmt::Write as _;
+
+use crate::{LayoutError, LayoutReport};
+
+/// Cycled through per field, so neighbouring fields in a slot never share a color.
+const PALETTE: [u8; 6] = [31, 32, 33, 34, 35, 36];
+/// Gray.
+const PADDING_COLOR: u8 = 90;
+const KEYS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+fn key(field: usize) -> char {
+    KEYS[field % KEYS.len()] as char
+}
+
+fn paint(text: &str, code: u8, color: bool) -> String {
+    if color {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+impl LayoutReport {
+    /// Draws every slot as a row of its 32 bytes, one character per byte keyed to the field
+    /// occupying it and `.` for padding, followed by a legend of the keys.
+    ///
+    /// Bytes are drawn most significant first, the way the slot reads as a word, so the first
+    /// field of a slot sits on the right. With `color` every field and the padding also get an
+    /// ANSI color, with `hex` slots are numbered `0x..`.
+    pub fn diagram(&self, color: bool, hex: bool) -> Result<String, LayoutError> {
+        let mut out = String::new();
+        let index = |slot: u64| {
+            if hex {
+                format!("{slot:#x}")
+            } else {
+                slot.to_string()
+            }
+        };
+        let width = index(self.total_slots.saturating_sub(1)).len();
+
+        let _ = writeln!(out, "{}:\n-------", self.struct_name);
+        for (slot, bytes) in self.byte_occupancy()?.iter().enumerate() {
+            // group runs of the same field so each only needs a single color escape
+            let mut runs: Vec<(Option<usize>, usize)> = vec![];
+            for byte in bytes.iter().rev() {
+                match runs.last_mut() {
+                    Some((field, len)) if field == byte => *len += 1,
+                    _ => runs.push((*byte, 1)),
+                }
+            }
+
+            let row = runs
+                .into_iter()
+                .map(|(field, len)| match field {
+                    Some(field) => paint(
+                        &key(field).to_string().repeat(len),
+                        PALETTE[field % PALETTE.len()],
+                        color,
+                    ),
+                    None => paint(&".".repeat(len), PADDING_COLOR, color),
+                })
+                .collect::<String>();
+            let _ = writeln!(out, "slot {:>width$} |{row}|", index(slot as u64));
+        }
+
+        for (i, field) in self.fields.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{} = {}: {}",
+                paint(&key(i).to_string(), PALETTE[i % PALETTE.len()], color),
+                field.name,
+                field.typ
+            );
+        }
+
+        Ok(out)
+    }
+}
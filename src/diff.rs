@@ -0,0 +1,47 @@
+//! Checks whether a new version of a struct or contract keeps the storage layout of the old
+//! one, so it can replace it in an upgrade.
+
+use crate::LayoutReport;
+
+/// Compares every layout in `old` with the layout of the same name in `new`, returning one line
+/// per field that moved, changed type or disappeared.
+///
+/// Fields appended after the old ones don't touch existing storage and aren't reported.
+/// Renamed fields are, the storage is still compatible but it's easy to mix up two fields that
+/// swapped places.
+pub fn diff_layouts(old: &[LayoutReport], new: &[LayoutReport]) -> Vec<String> {
+    let mut changes = vec![];
+    for old in old {
+        let name = &old.struct_name;
+        let Some(new) = new.iter().find(|new| new.struct_name == *name) else {
+            changes.push(format!("{name}: removed"));
+            continue;
+        };
+
+        for (i, old_field) in old.fields.iter().enumerate() {
+            let field = &old_field.name;
+            let Some(new_field) = new.fields.get(i) else {
+                changes.push(format!("{name}.{field}: removed"));
+                continue;
+            };
+
+            if new_field.name != *field {
+                changes.push(format!("{name}.{field}: renamed to {}", new_field.name));
+            }
+            if (new_field.slot, new_field.offset) != (old_field.slot, old_field.offset) {
+                changes.push(format!(
+                    "{name}.{field}: moved from slot {} offset {} to slot {} offset {}",
+                    old_field.slot, old_field.offset, new_field.slot, new_field.offset
+                ));
+            }
+            if new_field.typ != old_field.typ {
+                changes.push(format!(
+                    "{name}.{field}: type changed from {} to {}",
+                    old_field.typ, new_field.typ
+                ));
+            }
+        }
+    }
+
+    changes
+}
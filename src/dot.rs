@@ -0,0 +1,65 @@
+//! Graphviz graph of the computed layouts, with an edge from every struct to the structs it
+//! embeds.
+
+use std::fmt::Write as _;
+
+use crate::{LayoutReport, embedded_struct};
+
+/// Escapes the characters that have a meaning in a record label.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+
+    out
+}
+
+/// Renders `reports` as a `dot` digraph, one record node per layout with a row per slot
+/// listing the fields starting in it.
+pub fn dot(reports: &[LayoutReport]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph layout {{");
+    let _ = writeln!(out, "    node [shape=record];");
+
+    for report in reports {
+        let slots = report
+            .fields_by_slot()
+            .into_iter()
+            .map(|(slot, fields)| {
+                let fields = fields
+                    .iter()
+                    .map(|field| escape(&format!("{}: {}", field.name, field.typ)))
+                    .collect::<Vec<_>>();
+                format!("{{slot {slot}|{}\\l}}", fields.join("\\l"))
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{{{}|{}}}\"];",
+            report.struct_name,
+            escape(&report.struct_name),
+            slots.join("|")
+        );
+    }
+
+    for report in reports {
+        for field in &report.fields {
+            if let Some(inner) = embedded_struct(&field.typ)
+                && reports.iter().any(|other| other.struct_name == inner)
+            {
+                let _ = writeln!(
+                    out,
+                    "    \"{}\" -> \"{inner}\" [label=\"{}\"];",
+                    report.struct_name, field.name
+                );
+            }
+        }
+    }
+
+    let _ = writeln!(out, "}}");
+    out
+}
@@ -0,0 +1,88 @@
+//! Errors from parsing and laying out structs, so callers can tell them apart without matching
+//! on the message.
+
+use crate::solc::SolcVersion;
+
+/// Everything that can go wrong parsing struct definitions or computing their layout.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LayoutError {
+    /// A field refers to a struct that isn't defined anywhere in the input.
+    #[error("unknown struct: {0}")]
+    UnknownStruct(String),
+    /// A bare struct name declared in several contracts, none of them the one it's used in.
+    #[error("ambiguous struct {name}: could be any of {}", candidates.join(", "))]
+    AmbiguousStruct {
+        name: String,
+        candidates: Vec<String>,
+    },
+    /// A type name that doesn't parse, the message says what was wrong with it.
+    #[error("invalid type: {0}")]
+    InvalidType(String),
+    #[error("invalid mapping key type: mappings cannot be keyed by {kind} '{key}'")]
+    InvalidMappingKey { kind: &'static str, key: String },
+    /// A struct that ends up containing itself, the path goes from the outermost struct back
+    /// to the one it started at.
+    #[error(
+        "recursive struct '{}' has infinite storage size: {}",
+        .0[0],
+        .0.join(" -> ")
+    )]
+    CircularReference(Vec<String>),
+    /// Structs and arrays nested deeper than the recursion limit.
+    #[error("nested too deeply at {0}: exceeds the recursion limit")]
+    TooDeep(String),
+    #[error("array too large: would exceed u64 bits")]
+    ArrayTooLarge,
+    /// A struct too large to map byte by byte, by the slots it takes up.
+    #[error("{0} slots are too many to map byte by byte")]
+    TooManySlots(u64),
+    /// A struct declared twice in the same scope, with the lines of both declarations.
+    #[error("struct {name} is declared twice, on lines {} and {}", lines[0], lines[1])]
+    DuplicateStruct { name: String, lines: [usize; 2] },
+    /// A value type field that ended up across two slots. Never expected, it means the packing
+    /// itself is broken.
+    #[error("bug: value type field {0} spans a slot boundary")]
+    SplitAcrossSlots(String),
+    /// Solc AST json that's malformed or holds something that can't be laid out.
+    #[error("invalid ast: {0}")]
+    InvalidAst(String),
+    /// A feature used on `line` that only came with a later solc release than the one targeted.
+    #[error("line {line}: {feature} came with solc {since}, not available in {version}")]
+    UnavailableFeature {
+        feature: &'static str,
+        line: usize,
+        since: SolcVersion,
+        version: SolcVersion,
+    },
+    #[error("invalid solc version {0}, expected X.Y.Z")]
+    InvalidSolcVersion(String),
+    /// A mapping key that doesn't parse as, or doesn't fit in, the key type of the mapping.
+    #[error("invalid mapping key: {0}")]
+    InvalidKey(String),
+    /// A `field[key]..` expression that doesn't look up mapping entries.
+    #[error("invalid slot expression: {0}")]
+    InvalidSlotExpr(String),
+    /// A field path that isn't a top level field of any struct or contract.
+    #[error("no field {0} found")]
+    UnknownField(String),
+    /// A bare field name shared by several structs or contracts.
+    #[error("{field} is a field of both {} and {}, prefix it with one of them", owners[0], owners[1])]
+    AmbiguousField { field: String, owners: [String; 2] },
+    /// Json input that's malformed or doesn't have the shape expected.
+    #[error("invalid json: {0}")]
+    InvalidJson(String),
+    /// A forge layout without any of the structs or contracts laid out.
+    #[error("none of the structs or contracts appear in the forge layout")]
+    NotInForgeLayout,
+    /// A line of the source that isn't a struct header, a declaration or a closing brace.
+    #[error("line {line}: {msg}")]
+    ParseError { line: usize, msg: String },
+    /// An error in the type of a struct field or state variable, by the struct or contract it's
+    /// declared in.
+    #[error("error parsing {owner}.{field}: {source}")]
+    Field {
+        owner: String,
+        field: String,
+        source: Box<LayoutError>,
+    },
+}
@@ -0,0 +1,41 @@
+//! Reads a single struct from a json list of its fields, for callers that already have the
+//! field types and don't want to go through Solidity source.
+
+use serde_json::Value;
+
+use crate::{LayoutError, SolStruct};
+
+/// Builds the struct `name` out of `src`, a json array of `{"name": "..", "type": ".."}`
+/// objects in declaration order. Types are parsed the same way as in Solidity source.
+pub fn parse_fields_json(name: &str, src: &str) -> Result<SolStruct, LayoutError> {
+    let value: Value =
+        serde_json::from_str(src).map_err(|e| LayoutError::InvalidJson(e.to_string()))?;
+    let entries = value.as_array().ok_or_else(|| {
+        LayoutError::InvalidJson(
+            "fields json must be an array of {\"name\", \"type\"} objects".to_string(),
+        )
+    })?;
+
+    let mut fields = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        let field = entry["name"]
+            .as_str()
+            .ok_or_else(|| LayoutError::InvalidJson(format!("field {i} has no name")))?;
+        let typ = entry["type"]
+            .as_str()
+            .ok_or_else(|| LayoutError::InvalidJson(format!("field {field} has no type")))?;
+        let typ = typ.parse().map_err(|e| LayoutError::Field {
+            owner: name.to_string(),
+            field: field.to_string(),
+            source: Box::new(e),
+        })?;
+
+        fields.push((field.to_string(), typ, None));
+    }
+
+    Ok(SolStruct {
+        name: name.to_string(),
+        fields,
+        _inner: src.to_string(),
+    })
+}
@@ -0,0 +1,129 @@
+//! Checks the computed layouts against the storage layout json `forge inspect <Contract>
+//! storageLayout --json` prints.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{FieldLocation, LayoutError, SolStruct, field_locations, slot_count};
+
+/// Compares every struct that appears both in `structs` and in the `types` of the forge layout
+/// json in `src`, and the state variables in its `storage` against the contract of `contracts`
+/// they belong to, returning one line per slot/offset/label mismatch.
+pub fn compare_forge(
+    src: &str,
+    structs: &BTreeMap<String, SolStruct>,
+    contracts: &BTreeMap<String, SolStruct>,
+) -> Result<Vec<String>, LayoutError> {
+    let layout: Value = serde_json::from_str(src)
+        .map_err(|e| LayoutError::InvalidJson(format!("forge layout: {e}")))?;
+    // also accept a whole build artifact
+    let layout = layout.get("storageLayout").unwrap_or(&layout);
+    let types = layout["types"]
+        .as_object()
+        .ok_or_else(|| LayoutError::InvalidJson("forge layout has no types".to_string()))?;
+
+    let mut compared = 0;
+    let mut mismatches = vec![];
+    for typ in types.values() {
+        // labels look like `struct Hello.Foo`, the structs of a file given as `--from-ast` aren't
+        // qualified
+        let Some(name) = typ["label"]
+            .as_str()
+            .and_then(|label| label.strip_prefix("struct "))
+        else {
+            continue;
+        };
+        let Some(st) = structs
+            .get(name)
+            .or_else(|| structs.get(name.rsplit('.').next().unwrap_or(name)))
+        else {
+            continue;
+        };
+        compared += 1;
+
+        let members = typ["members"].as_array().ok_or_else(|| {
+            LayoutError::InvalidJson(format!("forge struct {name} has no members"))
+        })?;
+        let locations = field_locations(st, structs)?;
+        compare_members(name, members, &locations, &mut mismatches);
+
+        let bytes = slot_count(st, structs)? * 32;
+        let forge_bytes = typ["numberOfBytes"]
+            .as_str()
+            .and_then(|bytes| bytes.parse::<u64>().ok());
+        if Some(bytes) != forge_bytes {
+            mismatches.push(format!(
+                "{name}: {bytes} bytes but forge has {}",
+                typ["numberOfBytes"]
+            ));
+        }
+    }
+
+    // every state variable names its contract, like `src/Vault.sol:Vault`
+    let storage = layout["storage"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let contract = storage
+        .first()
+        .and_then(|variable| variable["contract"].as_str())
+        .map(|contract| contract.rsplit(':').next().unwrap_or(contract));
+    if let Some(name) = contract
+        && let Some(contract) = contracts.get(name)
+    {
+        compared += 1;
+        let locations = field_locations(contract, structs)?;
+        compare_members(name, storage, &locations, &mut mismatches);
+    }
+
+    if compared == 0 {
+        return Err(LayoutError::NotInForgeLayout);
+    }
+
+    Ok(mismatches)
+}
+
+/// Compares the `members` of a forge struct, or the `storage` of a contract, against the fields
+/// `locations` of `name` one by one.
+fn compare_members(
+    name: &str,
+    members: &[Value],
+    locations: &[FieldLocation],
+    mismatches: &mut Vec<String>,
+) {
+    for (i, member) in members.iter().enumerate() {
+        let label = member["label"].as_str().unwrap_or_default();
+        let slot = member["slot"].as_str().and_then(|slot| slot.parse().ok());
+        let offset = member["offset"].as_u64();
+
+        let Some(location) = locations.get(i) else {
+            mismatches.push(format!(
+                "{name}.{label}: missing, forge has it at index {i}"
+            ));
+            continue;
+        };
+
+        if location.name != label {
+            mismatches.push(format!(
+                "{name}: field {i} is {} but forge has {label}",
+                location.name
+            ));
+        }
+        if Some(location.slot) != slot {
+            mismatches.push(format!(
+                "{name}.{label}: slot {} but forge has {}",
+                location.slot, member["slot"]
+            ));
+        }
+        if Some(location.offset) != offset {
+            mismatches.push(format!(
+                "{name}.{label}: offset {} but forge has {}",
+                location.offset, member["offset"]
+            ));
+        }
+    }
+    for location in locations.iter().skip(members.len()) {
+        mismatches.push(format!("{name}.{}: not in the forge layout", location.name));
+    }
+}
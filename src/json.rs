@@ -0,0 +1,49 @@
+//! Machine readable form of the computed layouts.
+
+use serde_json::{Value, json};
+
+use crate::LayoutReport;
+
+/// Version of the shape of [`layouts_json`], bumped whenever it changes so consumers can fail
+/// fast on output they don't understand.
+pub const SCHEMA_VERSION: u64 = 3;
+
+/// Every layout of `reports` as a json array under `layouts`, beside the `schemaVersion` of the
+/// output.
+pub fn layouts_json<'a>(reports: impl IntoIterator<Item = &'a LayoutReport>) -> Value {
+    json!({
+        "schemaVersion": SCHEMA_VERSION,
+        "layouts": reports.into_iter().map(LayoutReport::to_json).collect::<Vec<_>>(),
+    })
+}
+
+impl LayoutReport {
+    /// The layout as a json object with the struct name, its slot and bit totals and every
+    /// field with its slot, byte offset, bits, type and NatSpec doc (`null` when undocumented).
+    ///
+    /// `byteMap` holds the 32 bytes of every slot, lowest first, as the index into `fields` of
+    /// the field stored there or `null` for padding. It's `null` itself for structs taking up
+    /// more than [`MAX_MAPPED_SLOTS`](crate::MAX_MAPPED_SLOTS).
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.struct_name,
+            "slots": self.total_slots,
+            "bits": self.total_bits,
+            "fields": self
+                .fields
+                .iter()
+                .map(|field| {
+                    json!({
+                        "name": field.name,
+                        "type": field.typ.to_string(),
+                        "slot": field.slot,
+                        "offset": field.offset,
+                        "bits": field.bits,
+                        "doc": field.doc,
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "byteMap": self.byte_occupancy().ok(),
+        })
+    }
+}
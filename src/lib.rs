@@ -0,0 +1,1896 @@
+//! Computes the storage layout of Solidity structs, the equivalent of `forge inspect <Contract>
+//! storage` for structs.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    num::ParseIntError,
+    str::FromStr,
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+pub mod ast;
+pub mod contract;
+pub mod csv;
+pub mod diagram;
+pub mod diff;
+pub mod dot;
+mod error;
+pub mod fields;
+pub mod forge;
+pub mod json;
+pub mod markdown;
+pub mod slot;
+pub mod solc;
+pub mod table;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::LayoutError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolType {
+    Uint(u16),
+    Int(u16),
+    Address,
+    Bool,
+    Bytes(u8),
+    BytesArbitrary,
+    /// Stored the same as `bytes`.
+    String,
+    Custom(SolStruct),
+    Custom2(String),
+    Mapping(Box<SolType>, Box<SolType>),
+    Array(Box<SolType>),
+    FixedArray(Box<SolType>, u64),
+}
+
+/// How deep structs and arrays can nest before sizing gives up, rather than overflowing the
+/// stack. Types nested deeper than this don't parse either.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Longest type name printed in full in error messages and table columns, deeply nested types
+/// are cut down to it.
+const MAX_TYPE_LABEL: usize = 80;
+
+/// `name` cut down to [`MAX_TYPE_LABEL`] characters, ending in `...` if it was cut.
+pub(crate) fn truncate_type(name: &str) -> String {
+    match name.char_indices().nth(MAX_TYPE_LABEL) {
+        Some((end, _)) => format!("{}...", &name[..end]),
+        None => name.to_string(),
+    }
+}
+
+/// Rounds `size` bits up to a whole number of slots.
+pub fn snap_to_upper_256(size: u64) -> u64 {
+    let over = size % 256;
+    let size = if over == 0 { size } else { size + 256 - over };
+    assert!(size.is_multiple_of(256));
+
+    size
+}
+
+// Storage layout rules: https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html
+//
+// - The first item in a storage slot is stored lower-order aligned.
+// - Value types use only as many bytes as are necessary to store them.
+// - If a value type does not fit the remaining part of a storage slot, it is stored in the next storage slot.
+// - Structs and array data always start a new slot and their items are packed tightly according to these rules.
+// - Items following struct or array data always start a new storage slot.
+impl SolType {
+    /// Value types pack into the remaining bits of the current slot, everything else anchors on
+    /// a fresh slot.
+    ///
+    /// Enums are value types too, once lowered to `uint8` by [`parse_structs`]. A bare
+    /// `Custom2` name is taken for a struct.
+    pub fn is_value_type(&self) -> bool {
+        matches!(
+            self,
+            Self::Uint(_) | Self::Int(_) | Self::Address | Self::Bool | Self::Bytes(_)
+        )
+    }
+
+    /// Whether `self` starts on a fresh slot, leaving the rest of the current one unused:
+    /// mappings, arrays, `bytes`, `string` and structs. The field after it starts on a fresh
+    /// slot as well.
+    pub fn forces_new_slot(&self) -> bool {
+        match self {
+            Self::Mapping(_, _)
+            | Self::Array(_)
+            | Self::FixedArray(_, _)
+            | Self::BytesArbitrary
+            | Self::String
+            | Self::Custom(_)
+            | Self::Custom2(_) => true,
+            Self::Uint(_) | Self::Int(_) | Self::Address | Self::Bool | Self::Bytes(_) => false,
+        }
+    }
+
+    /// Mappings can only be keyed by value types, `bytes`, and contract or enum names.
+    ///
+    /// A name can't be told apart from a struct name until all structs are known, that part is
+    /// checked when sizing the mapping.
+    fn validate_mapping_key(&self, key_type: &str) -> Result<(), LayoutError> {
+        let kind = match self {
+            Self::Custom(_) => "struct",
+            Self::Array(_) | Self::FixedArray(_, _) => "array",
+            Self::Mapping(_, _) => "mapping",
+            _ => return Ok(()),
+        };
+
+        Err(LayoutError::InvalidMappingKey {
+            kind,
+            key: key_type.to_string(),
+        })
+    }
+
+    /// Makes sure every struct named in `self` is defined. Mapping values and dynamic array
+    /// elements live elsewhere and never get sized, so they'd slip through otherwise.
+    fn check_resolvable(
+        &self,
+        all_structs: &BTreeMap<String, SolStruct>,
+        max_depth: usize,
+    ) -> Result<(), LayoutError> {
+        if max_depth == 0 {
+            return Err(LayoutError::TooDeep(truncate_type(&self.to_string())));
+        }
+
+        match self {
+            Self::Custom2(st_name) if !all_structs.contains_key(st_name) => {
+                Err(LayoutError::UnknownStruct(st_name.clone()))
+            }
+            Self::Mapping(_, inner) | Self::Array(inner) | Self::FixedArray(inner, _) => {
+                inner.check_resolvable(all_structs, max_depth - 1)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Errors if a struct stored inline in `self` ends up containing itself, which would take up
+    /// infinite storage. Mappings and dynamic arrays keep their values elsewhere, so those can
+    /// refer back to the struct.
+    ///
+    /// `path` holds the structs entered so far, outermost first. Past `max_depth` structs and
+    /// arrays it stops looking, as sizing reports those.
+    fn check_cycles(
+        &self,
+        all_structs: &BTreeMap<String, SolStruct>,
+        path: &mut Vec<String>,
+        max_depth: usize,
+    ) -> Result<(), LayoutError> {
+        if max_depth == 0 {
+            return Ok(());
+        }
+
+        let st = match self {
+            Self::Custom(st) => st,
+            // unknown structs are reported when sizing
+            Self::Custom2(st_name) => match all_structs.get(st_name) {
+                Some(st) => st,
+                None => return Ok(()),
+            },
+            Self::FixedArray(inner, _) => {
+                return inner.check_cycles(all_structs, path, max_depth - 1);
+            }
+            _ => return Ok(()),
+        };
+
+        if let Some(start) = path.iter().position(|name| *name == st.name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(st.name.clone());
+            return Err(LayoutError::CircularReference(cycle));
+        }
+
+        path.push(st.name.clone());
+        for (_, typ, _) in &st.fields {
+            typ.check_cycles(all_structs, path, max_depth - 1)?;
+        }
+        path.pop();
+
+        Ok(())
+    }
+
+    /// Size of the type in bits, structs named by `Custom2` are resolved through `all_structs`.
+    pub fn size(&self, all_structs: &BTreeMap<String, SolStruct>) -> Result<u64, LayoutError> {
+        self.check_cycles(all_structs, &mut vec![], DEFAULT_MAX_DEPTH)?;
+        self.size_with_max_depth(all_structs, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Size of the type in bytes, rounded up.
+    pub fn size_bytes(
+        &self,
+        all_structs: &BTreeMap<String, SolStruct>,
+    ) -> Result<u64, LayoutError> {
+        Ok(self.size(all_structs)?.div_ceil(8))
+    }
+
+    /// Number of slots the type takes up, rounded up to whole slots.
+    pub fn size_slots(
+        &self,
+        all_structs: &BTreeMap<String, SolStruct>,
+    ) -> Result<u64, LayoutError> {
+        Ok(snap_to_upper_256(self.size(all_structs)?) / 256)
+    }
+
+    /// Like [`SolType::size`], but errors out once structs and arrays are nested more than
+    /// `max_depth` levels deep instead of the default 256.
+    pub fn size_with_max_depth(
+        &self,
+        all_structs: &BTreeMap<String, SolStruct>,
+        max_depth: usize,
+    ) -> Result<u64, LayoutError> {
+        if max_depth == 0 {
+            return Err(LayoutError::TooDeep(truncate_type(&self.to_string())));
+        }
+
+        Ok(match self {
+            Self::Uint(size) => (*size).into(),
+            Self::Int(size) => (*size).into(),
+            Self::Address => (20u32 * 8).into(),
+            // a whole byte, not a bit
+            Self::Bool => 8,
+            Self::Bytes(size) => *size as u64 * 8,
+            Self::BytesArbitrary | Self::String => 256,
+            Self::Custom(sol_struct) => struct_bits(sol_struct, all_structs, max_depth)?,
+            Self::Custom2(st_name) => struct_bits(
+                all_structs
+                    .get(st_name)
+                    .ok_or_else(|| LayoutError::UnknownStruct(st_name.clone()))?,
+                all_structs,
+                max_depth,
+            )?,
+            Self::Mapping(key, value) => {
+                if let Self::Custom2(st_name) = key.as_ref()
+                    && all_structs.contains_key(st_name)
+                {
+                    return Err(LayoutError::InvalidMappingKey {
+                        kind: "struct",
+                        key: st_name.clone(),
+                    });
+                }
+                value.check_resolvable(all_structs, max_depth)?;
+
+                256
+            }
+            Self::Array(inner) => {
+                inner.check_resolvable(all_structs, max_depth)?;
+
+                256
+            }
+            // laid out the same as a struct field, small elements pack into shared slots
+            Self::FixedArray(_, _) => {
+                let mut size = 0;
+                let mut current_word_bits_allocated = 0;
+                update_state(
+                    self,
+                    &mut current_word_bits_allocated,
+                    &mut size,
+                    all_structs,
+                    max_depth,
+                )?;
+
+                size
+            }
+        })
+    }
+}
+
+/// Allocates `typ` right after the `size` bits laid out so far, where the last slot already has
+/// `current_word_bits_allocated` bits taken.
+fn update_state(
+    typ: &SolType,
+    current_word_bits_allocated: &mut u64,
+    size: &mut u64,
+    all_structs: &BTreeMap<String, SolStruct>,
+    max_depth: usize,
+) -> Result<(), LayoutError> {
+    if max_depth == 0 {
+        return Err(LayoutError::TooDeep(truncate_type(&typ.to_string())));
+    }
+    let remainder_bits = 256 - *current_word_bits_allocated;
+
+    match typ {
+        // Value types use up only as many bytes as necessary if available, or
+        // start on new slot if not enough space.
+        SolType::Uint(_)
+        | SolType::Int(_)
+        | SolType::Address
+        | SolType::Bool
+        | SolType::Bytes(_) => {
+            let bits_needed = typ.size(all_structs)?;
+            if bits_needed <= remainder_bits {
+                *current_word_bits_allocated += bits_needed;
+                *size += bits_needed;
+            } else {
+                // move to next slot
+                *current_word_bits_allocated = 0;
+                *size += remainder_bits;
+                // allocate bits in next slot
+                *size += bits_needed;
+                *current_word_bits_allocated += bits_needed;
+            }
+        }
+        // Fixed array types are inlined
+        SolType::FixedArray(sol_type, len) => {
+            // move to next slot
+            *current_word_bits_allocated = 0;
+            *size = snap_to_upper_256(*size);
+            if *len == 0 {
+                return Ok(());
+            }
+
+            // worked out in one go instead of element by element, the length can be huge
+            let bits = if sol_type.is_value_type() {
+                let element_bits = sol_type.size(all_structs)?;
+                let per_slot = 256 / element_bits;
+                // every slot but the last one is full
+                let (full_slots, last) = ((len - 1) / per_slot, (len - 1) % per_slot + 1);
+
+                full_slots
+                    .checked_mul(256)
+                    .and_then(|bits| bits.checked_add(last * element_bits))
+                    .ok_or(LayoutError::ArrayTooLarge)?
+            } else {
+                // every element starts on a fresh slot, so they all take up the same
+                let mut element_bits = 0;
+                update_state(
+                    sol_type,
+                    current_word_bits_allocated,
+                    &mut element_bits,
+                    all_structs,
+                    max_depth - 1,
+                )?;
+
+                snap_to_upper_256(element_bits)
+                    .checked_mul(len - 1)
+                    .and_then(|bits| bits.checked_add(element_bits))
+                    .ok_or(LayoutError::ArrayTooLarge)?
+            };
+            *size = size.checked_add(bits).ok_or(LayoutError::ArrayTooLarge)?;
+
+            // items following array data always start a new slot
+            *current_word_bits_allocated = 0;
+            *size = snap_to_upper_256(*size);
+        }
+        // Mapping, Dynamic size array, arbitrary bytes and strings, all take up the next full
+        // slot.
+        SolType::Mapping(_, _) | SolType::Array(_) | SolType::BytesArbitrary | SolType::String => {
+            *current_word_bits_allocated = 0;
+            *size = snap_to_upper_256(*size);
+            *size += typ.size(all_structs)?;
+        }
+        // Structs are packed tightly according to the rules above.
+        // And they always start on a new slot.
+        // Items following structs always start on a new slot
+        SolType::Custom(_) | SolType::Custom2(_) => {
+            *current_word_bits_allocated = 0;
+            *size = snap_to_upper_256(*size);
+            *size += typ.size_with_max_depth(all_structs, max_depth)?;
+            *size = snap_to_upper_256(*size);
+        }
+    }
+
+    Ok(())
+}
+
+/// Bits the fields of `st` take up, packed one after the other.
+fn struct_bits(
+    st: &SolStruct,
+    all_structs: &BTreeMap<String, SolStruct>,
+    max_depth: usize,
+) -> Result<u64, LayoutError> {
+    let mut size = 0;
+    let mut current_word_bits_allocated = 0;
+    for (_, typ, _) in &st.fields {
+        update_state(
+            typ,
+            &mut current_word_bits_allocated,
+            &mut size,
+            all_structs,
+            max_depth - 1,
+        )?;
+    }
+
+    Ok(size)
+}
+
+/// Renders the canonical Solidity type name. Types nested deeper than [`DEFAULT_MAX_DEPTH`]
+/// end in `...` there.
+impl fmt::Display for SolType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_name(f, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl SolType {
+    fn write_name(&self, f: &mut fmt::Formatter<'_>, max_depth: usize) -> fmt::Result {
+        if max_depth == 0 {
+            return write!(f, "...");
+        }
+
+        match self {
+            Self::Uint(size) => write!(f, "uint{size}"),
+            Self::Int(size) => write!(f, "int{size}"),
+            Self::Address => write!(f, "address"),
+            Self::Bool => write!(f, "bool"),
+            Self::Bytes(size) => write!(f, "bytes{size}"),
+            Self::BytesArbitrary => write!(f, "bytes"),
+            Self::String => write!(f, "string"),
+            Self::Custom(sol_struct) => write!(f, "{}", sol_struct.name),
+            Self::Custom2(st_name) => write!(f, "{st_name}"),
+            Self::Mapping(key, value) => {
+                write!(f, "mapping(")?;
+                key.write_name(f, max_depth - 1)?;
+                write!(f, " => ")?;
+                value.write_name(f, max_depth - 1)?;
+                write!(f, ")")
+            }
+            Self::Array(sol_type) => {
+                sol_type.write_name(f, max_depth - 1)?;
+                write!(f, "[]")
+            }
+            Self::FixedArray(sol_type, len) => {
+                sol_type.write_name(f, max_depth - 1)?;
+                write!(f, "[{len}]")
+            }
+        }
+    }
+}
+
+/// Splits `mapping(K => V)` into `K` and `V`. The value can be any type, mappings and arrays of
+/// structs included, as long as its parens balance.
+fn split_mapping(s: &str) -> Option<(&str, &str)> {
+    let inner = s
+        .strip_prefix("mapping")?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+
+    let mut depth = 0;
+    let mut arrow = None;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            // the paren after `mapping` closed before the end, `mapping(K => V) x)`
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            // keys can't be mappings, so the first top level arrow is the one
+            '=' if depth == 0 && arrow.is_none() && inner[i..].starts_with("=>") => {
+                arrow = Some(i);
+            }
+            _ => {}
+        }
+    }
+    let arrow = arrow.filter(|_| depth == 0)?;
+
+    Some((inner[..arrow].trim(), inner[arrow + 2..].trim()))
+}
+
+/// The width of a `uintN`, `intN` or `bytesN` type name.
+fn parse_width<T: FromStr<Err = ParseIntError>>(s: &str, prefix: &str) -> Result<T, LayoutError> {
+    s[prefix.len()..]
+        .parse()
+        .map_err(|e| LayoutError::InvalidType(format!("error parsing the width of {s}: {e}")))
+}
+
+/// Splits `T[len]` into `T` and `len`, for the last pair of brackets in `s`.
+fn split_array_suffix(s: &str) -> Option<(&str, &str)> {
+    let inner = s.strip_suffix(']')?;
+
+    let mut depth = 0;
+    for (i, c) in inner.char_indices().rev() {
+        match c {
+            ']' => depth += 1,
+            '[' if depth == 0 => return Some((&inner[..i], &inner[i + 1..])),
+            '[' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Evaluates an integer expression of `+`, `-`, `*` and parentheses over literals and named
+/// `constants`, the length of a fixed array like `N * 2`.
+struct LengthExpr<'a> {
+    rest: &'a str,
+    constants: &'a BTreeMap<String, u64>,
+}
+
+impl LengthExpr<'_> {
+    fn eval(expr: &str, constants: &BTreeMap<String, u64>) -> Result<u64, String> {
+        let mut parser = LengthExpr {
+            rest: expr,
+            constants,
+        };
+        let value = parser.sum()?;
+        match parser.peek() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected {c}")),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        self.rest.chars().next()
+    }
+
+    fn sum(&mut self) -> Result<u64, String> {
+        let mut value = self.product()?;
+        loop {
+            value = match self.peek() {
+                Some('+') => {
+                    self.rest = &self.rest[1..];
+                    value.checked_add(self.product()?).ok_or("overflows u64")?
+                }
+                Some('-') => {
+                    self.rest = &self.rest[1..];
+                    value.checked_sub(self.product()?).ok_or("goes negative")?
+                }
+                _ => return Ok(value),
+            };
+        }
+    }
+
+    fn product(&mut self) -> Result<u64, String> {
+        let mut value = self.operand()?;
+        while self.peek() == Some('*') {
+            self.rest = &self.rest[1..];
+            value = value.checked_mul(self.operand()?).ok_or("overflows u64")?;
+        }
+
+        Ok(value)
+    }
+
+    fn operand(&mut self) -> Result<u64, String> {
+        if self.peek() == Some('(') {
+            self.rest = &self.rest[1..];
+            let value = self.sum()?;
+            if self.peek() != Some(')') {
+                return Err("unbalanced parentheses".to_string());
+            }
+            self.rest = &self.rest[1..];
+            return Ok(value);
+        }
+
+        let len = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        let (token, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        match token.chars().next() {
+            None => Err("missing operand".to_string()),
+            Some(c) if c.is_ascii_digit() => token
+                .replace('_', "")
+                .parse()
+                .map_err(|e| format!("{token}: {e}")),
+            Some(_) => self
+                .constants
+                .get(token)
+                .copied()
+                .ok_or_else(|| format!("{token} isn't a known constant")),
+        }
+    }
+}
+
+impl FromStr for SolType {
+    type Err = LayoutError;
+
+    /// Parses what `Display` prints back into the same type, bare `uint`/`int` are normalized to
+    /// their 256 bit width.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_constants(s, &BTreeMap::new())
+    }
+}
+
+impl SolType {
+    /// Like parsing with [`FromStr`], with the lengths of fixed arrays allowed to be constant
+    /// expressions over `constants`, `uint256[N * 2]`.
+    pub fn parse_with_constants(
+        s: &str,
+        constants: &BTreeMap<String, u64>,
+    ) -> Result<Self, LayoutError> {
+        // point at the whole type, not the part of it nested too deeply
+        Self::parse_with_depth(s, constants, DEFAULT_MAX_DEPTH).map_err(|e| match e {
+            LayoutError::TooDeep(_) => LayoutError::TooDeep(truncate_type(s.trim())),
+            e => e,
+        })
+    }
+
+    /// [`SolType::parse_with_constants`], failing on mappings and arrays nested more than
+    /// `max_depth` levels deep.
+    fn parse_with_depth(
+        s: &str,
+        constants: &BTreeMap<String, u64>,
+        max_depth: usize,
+    ) -> Result<Self, LayoutError> {
+        // the width arms below take the width out of `s`, so it has to be trimmed already
+        let s = s.trim();
+        if max_depth == 0 {
+            return Err(LayoutError::TooDeep(truncate_type(s)));
+        }
+
+        Ok(match s {
+            "uint" => Self::Uint(256),
+            "int" => Self::Int(256),
+            // payable only restricts what the address can be used for, it's stored the same
+            "address" | "address payable" => Self::Address,
+            "bool" => Self::Bool,
+            "bytes" => Self::BytesArbitrary,
+            "string" => Self::String,
+            "bytes1" | "bytes2" | "bytes3" | "bytes4" | "bytes5" | "bytes6" | "bytes7"
+            | "bytes8" | "bytes9" | "bytes10" | "bytes11" | "bytes12" | "bytes13" | "bytes14"
+            | "bytes15" | "bytes16" | "bytes17" | "bytes18" | "bytes19" | "bytes20" | "bytes21"
+            | "bytes22" | "bytes23" | "bytes24" | "bytes25" | "bytes26" | "bytes27" | "bytes28"
+            | "bytes29" | "bytes30" | "bytes31" | "bytes32" => {
+                Self::Bytes(parse_width(s, "bytes")?)
+            }
+            "uint8" | "uint16" | "uint24" | "uint32" | "uint40" | "uint48" | "uint56"
+            | "uint64" | "uint72" | "uint80" | "uint88" | "uint96" | "uint104" | "uint112"
+            | "uint120" | "uint128" | "uint136" | "uint144" | "uint152" | "uint160" | "uint168"
+            | "uint176" | "uint184" | "uint192" | "uint200" | "uint208" | "uint216" | "uint224"
+            | "uint232" | "uint240" | "uint248" | "uint256" => Self::Uint(parse_width(s, "uint")?),
+            "int8" | "int16" | "int24" | "int32" | "int40" | "int48" | "int56" | "int64"
+            | "int72" | "int80" | "int88" | "int96" | "int104" | "int112" | "int120" | "int128"
+            | "int136" | "int144" | "int152" | "int160" | "int168" | "int176" | "int184"
+            | "int192" | "int200" | "int208" | "int216" | "int224" | "int232" | "int240"
+            | "int248" | "int256" => Self::Int(parse_width(s, "int")?),
+            // the last pair of brackets is the outermost array, `T[2][3]` holds 3 `T[2]`s
+            s if s.ends_with(']') => {
+                let (inner_type, len) = split_array_suffix(s).ok_or_else(|| {
+                    LayoutError::InvalidType(format!("unbalanced brackets in array type: {s}"))
+                })?;
+                let inner_type = Box::new(Self::parse_with_depth(
+                    inner_type,
+                    constants,
+                    max_depth - 1,
+                )?);
+
+                match len.trim() {
+                    "" => Self::Array(inner_type),
+                    len => match LengthExpr::eval(len, constants) {
+                        // solc rejects these, they'd take up no storage at all
+                        Ok(0) => {
+                            return Err(LayoutError::InvalidType(format!(
+                                "invalid array size: 0 in {s}"
+                            )));
+                        }
+                        Ok(len) => Self::FixedArray(inner_type, len),
+                        Err(e) => {
+                            return Err(LayoutError::InvalidType(format!(
+                                "error parsing array length {len}: {e}"
+                            )));
+                        }
+                    },
+                }
+            }
+            s if s
+                .strip_prefix("mapping")
+                .is_some_and(|rest| rest.trim_start().starts_with('(')) =>
+            {
+                let (key_type, value_type) = split_mapping(s)
+                    .ok_or_else(|| LayoutError::InvalidType(format!("invalid mapping: {s}")))?;
+
+                let key = Self::parse_with_depth(key_type, constants, max_depth - 1)?;
+                key.validate_mapping_key(key_type)?;
+
+                Self::Mapping(
+                    Box::new(key),
+                    Box::new(Self::parse_with_depth(
+                        value_type,
+                        constants,
+                        max_depth - 1,
+                    )?),
+                )
+            }
+            "" => return Err(LayoutError::InvalidType("missing type name".to_string())),
+            // a struct, enum or contract name, possibly qualified like `Vault.Order`
+            _ if s.split('.').all(|part| {
+                part.bytes().all(is_ident)
+                    && part.bytes().next().is_some_and(|c| !c.is_ascii_digit())
+            }) =>
+            {
+                Self::Custom2(s.to_string())
+            }
+            _ => return Err(LayoutError::InvalidType(format!("{s} isn't a type name"))),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SolStruct {
+    pub name: String,
+    /// Name, type and `///` NatSpec doc of every field, in declaration order.
+    pub fields: Vec<(String, SolType, Option<String>)>,
+    _inner: String,
+}
+
+/// Structs are equal when their names and fields are, however their source was formatted.
+impl PartialEq for SolStruct {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.fields == other.fields
+    }
+}
+
+impl Eq for SolStruct {}
+
+static STRUCT_HEADER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*struct\s+(?<name>\w+)").expect("valid struct header regex"));
+
+/// Whether `c` can be part of an identifier.
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$'
+}
+
+/// A struct declaration in the source.
+struct StructChunk {
+    /// The line its header is on.
+    line: usize,
+    name: String,
+    /// The contract, library or interface it is declared in, `None` at the top level.
+    scope: Option<String>,
+    /// From the `struct` keyword to the closing brace.
+    src: String,
+}
+
+/// Finds every struct declared in `src`, along with the contract, library or interface it's
+/// declared in. Comments and string literals are skipped, braces and keywords inside of them
+/// don't count.
+fn scan_structs(src: &str) -> Vec<StructChunk> {
+    // files saved on Windows can start with a byte order mark, which would hide the first header
+    let src = src.strip_prefix('\u{feff}').unwrap_or(src);
+    let bytes = src.as_bytes();
+
+    let mut chunks = vec![];
+    // every enclosing scope, the depth outside of it, and whether its body opened
+    let mut scopes: Vec<(String, u64, bool)> = vec![];
+    // the struct being read, its chunk so far, the depth outside of it and whether its body
+    // opened
+    let mut current: Option<(StructChunk, usize, u64, bool)> = None;
+    let mut depth = 0u64;
+    let mut line = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => line += 1,
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = src[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + end + 4);
+                line += src[i..end].matches('\n').count();
+                i = end;
+                continue;
+            }
+            quote @ (b'"' | b'\'') => {
+                // string literals don't span lines, skip until the closing quote
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if i < bytes.len() && bytes[i] == b'\n' {
+                    continue;
+                }
+            }
+            b'{' => {
+                depth += 1;
+                // the first `{` after a header opens its body
+                match &mut current {
+                    Some((_, _, _, opened)) => *opened = true,
+                    None => {
+                        if let Some((_, _, opened)) = scopes.last_mut() {
+                            *opened = true;
+                        }
+                    }
+                }
+            }
+            b'}' => {
+                depth = depth.saturating_sub(1);
+                if current
+                    .as_ref()
+                    .is_some_and(|&(_, _, outside, opened)| opened && depth <= outside)
+                    && let Some((mut chunk, start, _, _)) = current.take()
+                {
+                    chunk.src = src[start..=i].to_string();
+                    chunks.push(chunk);
+                }
+                while current.is_none()
+                    && scopes
+                        .last()
+                        .is_some_and(|&(_, outside, opened)| opened && depth <= outside)
+                {
+                    scopes.pop();
+                }
+            }
+            c if is_ident(c) && (i == 0 || !is_ident(bytes[i - 1])) => {
+                let end = src[i..]
+                    .find(|c: char| !c.is_ascii() || !is_ident(c as u8))
+                    .map_or(bytes.len(), |end| i + end);
+                let keyword = &src[i..end];
+                let rest = src[end..].trim_start();
+                let name = &rest[..rest
+                    .find(|c: char| !c.is_ascii() || !is_ident(c as u8))
+                    .unwrap_or(rest.len())];
+
+                // a `struct` without a name is kept too, for `parse_struct` to report
+                if current.is_none() && (!name.is_empty() || keyword == "struct") {
+                    match keyword {
+                        "struct" => {
+                            let chunk = StructChunk {
+                                line,
+                                name: name.to_string(),
+                                scope: scopes.last().map(|(scope, _, _)| scope.clone()),
+                                src: String::new(),
+                            };
+                            current = Some((chunk, i, depth, false));
+                        }
+                        "contract" | "library" | "interface" => {
+                            scopes.push((name.to_string(), depth, false));
+                        }
+                        _ => {}
+                    }
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if let Some((mut chunk, start, _, _)) = current {
+        chunk.src = src[start..].trim_end().to_string();
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// Splits `src` into one chunk per struct definition, along with the line number of its header.
+///
+/// Structs are picked up wherever they are declared, so ones nested in a `contract { ... }` or
+/// `library { ... }` body, like the `AppStorage` of the diamond storage pattern, are extracted
+/// alongside top-level ones, even when they share a line with it or with each other. Everything
+/// outside a struct is skipped, so a whole `.sol` file with its SPDX comment, pragmas and
+/// imports can be passed in as is.
+///
+/// Line numbers start at 1. Every chunk starts at its `struct` keyword and ends with the closing
+/// brace, ready for [`parse_struct`]. An unterminated struct at the end of `src` is kept as far
+/// as it goes, for [`parse_struct`] to report.
+pub fn chunk_structs(src: &str) -> Vec<(usize, String)> {
+    scan_structs(src)
+        .into_iter()
+        .map(|chunk| (chunk.line, chunk.src))
+        .collect()
+}
+
+/// Data location keywords, meaningless for a struct field and dropped from its type.
+const DATA_LOCATIONS: &[&str] = &["memory", "storage", "calldata"];
+
+/// Drops the `/* ... */` comments that open and close on `line`.
+fn strip_block_comments(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some((code, comment)) = rest.split_once("/*") {
+        let Some((_, after)) = comment.split_once("*/") else {
+            break;
+        };
+        out.push_str(code);
+        out.push(' ');
+        rest = after;
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// The first struct name in `typ` that isn't one of `known`.
+fn unknown_name<'a>(typ: &'a SolType, known: &BTreeSet<String>) -> Option<&'a str> {
+    match typ {
+        // `Lib.Name` can stand for a `Name` declared at the top level, see [`qualify`]
+        SolType::Custom2(name) => {
+            (!known.contains(name) && !known.contains(unqualified(name))).then_some(name.as_str())
+        }
+        SolType::Mapping(key, value) => {
+            unknown_name(key, known).or_else(|| unknown_name(value, known))
+        }
+        SolType::Array(inner) | SolType::FixedArray(inner, _) => unknown_name(inner, known),
+        _ => None,
+    }
+}
+
+/// Parses a single struct definition, like a chunk from [`chunk_structs`], with `first_line`
+/// the line number of its header for errors.
+///
+/// `src` holds the `struct Name {` header, its brace possibly on the next line, any number of
+/// `type name;` declarations with their comments, and the closing brace. A header or body that's
+/// missing is a [`LayoutError::ParseError`]. Names that aren't builtin types are taken for structs as
+/// `Custom2` and only resolved when sizing, enums included, which [`parse_structs`] lowers to
+/// `uint8` as it sees their declarations.
+pub fn parse_struct(src: &str, first_line: usize) -> Result<SolStruct, LayoutError> {
+    parse_struct_with(src, first_line, &BTreeMap::new(), None)
+}
+
+/// With `known` set, a field named by anything but one of those types fails right away
+/// instead of when the struct gets sized. Array lengths can name `constants`.
+fn parse_struct_with(
+    src: &str,
+    first_line: usize,
+    constants: &BTreeMap<String, u64>,
+    known: Option<&BTreeSet<String>>,
+) -> Result<SolStruct, LayoutError> {
+    let mut struct_name = String::new();
+    let mut fields = vec![];
+    let mut in_block_comment = false;
+    // `///` lines waiting for the field they document
+    let mut doc: Vec<&str> = vec![];
+    // a declaration wrapped over several lines so far, and the line it started on
+    let mut pending = String::new();
+    let mut pending_line = 0;
+    // the header went without its opening brace, it has to start the next line
+    let mut awaiting_brace = false;
+    let mut closed = false;
+
+    for (i, line) in src.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(text) = line.trim().strip_prefix("///")
+            && !in_block_comment
+        {
+            doc.push(text.trim());
+            continue;
+        }
+
+        // drop comments, braces and keywords inside of them don't matter
+        let line = strip_block_comments(line);
+        let line = line
+            .split_once("//")
+            .map_or(&*line, |(code, _)| code)
+            .trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if in_block_comment || line.starts_with("/*") {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+
+        let line = if let Some(captures) = STRUCT_HEADER_REGEX.captures(line) {
+            if awaiting_brace {
+                return Err(LayoutError::ParseError {
+                    line: first_line,
+                    msg: format!("struct {struct_name} has no body"),
+                });
+            }
+            struct_name = captures["name"].to_string();
+            // documents the struct itself
+            doc.clear();
+            let rest = line[captures.get(0).map_or(0, |m| m.end())..].trim_start();
+            if rest.is_empty() {
+                awaiting_brace = true;
+                continue;
+            }
+            // fields can follow the opening brace, `struct Foo { uint a; }`
+            rest.strip_prefix('{')
+                .ok_or_else(|| LayoutError::ParseError {
+                    line: first_line + i,
+                    msg: format!("invalid struct header: {line}"),
+                })?
+                .trim()
+        } else if awaiting_brace {
+            awaiting_brace = false;
+            line.strip_prefix('{')
+                .ok_or_else(|| LayoutError::ParseError {
+                    line: first_line + i,
+                    msg: format!("expected {{ after struct {struct_name}, found: {line}"),
+                })?
+                .trim()
+        } else if struct_name.is_empty() {
+            let msg = if line.starts_with("struct") {
+                format!("struct without a name: {line}")
+            } else {
+                format!("expected a struct header, found: {line}")
+            };
+            return Err(LayoutError::ParseError {
+                line: first_line + i,
+                msg,
+            });
+        } else {
+            line
+        };
+
+        if line.is_empty() {
+            continue;
+        }
+
+        // formatters wrap long declarations, they go on until their `;`
+        let continued = !pending.is_empty();
+        if !continued {
+            pending_line = first_line + i;
+        }
+        let joined;
+        let line = if continued {
+            joined = format!("{} {line}", std::mem::take(&mut pending));
+            joined.as_str()
+        } else {
+            line
+        };
+        let start_line = pending_line;
+        closed = line.ends_with('}');
+        let invalid_line = |line: &str| LayoutError::ParseError {
+            line: start_line,
+            msg: format!("invalid line: {line}"),
+        };
+
+        if let Some((decls, rest)) = line.strip_suffix('}').unwrap_or(line).rsplit_once(';') {
+            // stray `;` and `,` from hand edited input leave empty declarations behind
+            let is_stray = |c: char| c == ',' || c.is_whitespace();
+
+            // a line can hold several declarations, the start of one continued on the next
+            // line, or the closing brace of the struct
+            let rest = rest.trim_matches(is_stray);
+            if !rest.is_empty() {
+                if line.ends_with('}') {
+                    return Err(invalid_line(line));
+                }
+                pending = rest.to_string();
+                pending_line = first_line + i;
+            }
+
+            for decl in decls.split(';') {
+                let splits = decl
+                    .trim_matches(is_stray)
+                    .split_whitespace()
+                    .collect::<Vec<_>>();
+                let Some((field, typ)) = splits.split_last() else {
+                    continue;
+                };
+                if typ.is_empty() {
+                    return Err(invalid_line(line));
+                }
+
+                // declarations copied from a function signature carry a data location
+                let typ = typ
+                    .iter()
+                    .filter(|token| !DATA_LOCATIONS.contains(token))
+                    .copied()
+                    .collect::<Vec<_>>();
+
+                let typ = SolType::parse_with_constants(&typ.join(" "), constants)?;
+                if let Some(name) = known.and_then(|known| unknown_name(&typ, known)) {
+                    return Err(LayoutError::ParseError {
+                        line: first_line + i,
+                        msg: format!("unknown type {name} of field {field}"),
+                    });
+                }
+
+                let field_doc = (!doc.is_empty()).then(|| doc.join("\n"));
+                fields.push((field.to_string(), typ, field_doc));
+                doc.clear();
+            }
+        } else if line.trim() == "}" {
+            // do nothing
+        } else if line.ends_with('}') {
+            return Err(invalid_line(line));
+        } else {
+            pending = line.to_string();
+        }
+    }
+    if !pending.is_empty() {
+        return Err(LayoutError::ParseError {
+            line: pending_line,
+            msg: format!("unterminated declaration: {pending}"),
+        });
+    }
+    if awaiting_brace {
+        return Err(LayoutError::ParseError {
+            line: first_line,
+            msg: format!("struct {struct_name} has no body"),
+        });
+    }
+    if !closed {
+        return Err(LayoutError::ParseError {
+            line: first_line,
+            msg: format!("struct {struct_name} is missing its closing brace"),
+        });
+    }
+
+    Ok(SolStruct {
+        name: struct_name,
+        fields,
+        _inner: src.to_string(),
+    })
+}
+
+/// Number of slots `st` takes up.
+pub fn slot_count(
+    st: &SolStruct,
+    all_structs: &BTreeMap<String, SolStruct>,
+) -> Result<u64, LayoutError> {
+    SolType::Custom(st.clone()).size_slots(all_structs)
+}
+
+/// Number of SSTOREs it takes to write every field of `st` once, into storage that's still
+/// zero.
+///
+/// Value types packed into the same slot share a single write. Mappings and dynamic arrays are
+/// counted as getting a single entry, which lives at a hashed slot of its own: a dynamic array
+/// also writes its length, the slot of a mapping is never written to. `bytes` are assumed short
+/// enough (31 bytes at most) to be stored inline. An entry holding a struct that's already being
+/// written further up, like the children of a tree node, counts as a single write.
+pub fn sstore_count(
+    st: &SolStruct,
+    all_structs: &BTreeMap<String, SolStruct>,
+) -> Result<u64, LayoutError> {
+    let mut walk = SstoreWalk {
+        all_structs,
+        locations: BTreeMap::new(),
+        path: vec![],
+    };
+    walk.struct_sstores(st, DEFAULT_MAX_DEPTH)
+}
+
+/// State for [`sstore_count`]: the structs being written on the way down, and the fields of the
+/// structs seen so far, so they're only laid out once.
+struct SstoreWalk<'a> {
+    all_structs: &'a BTreeMap<String, SolStruct>,
+    locations: BTreeMap<String, Vec<FieldLocation>>,
+    path: Vec<String>,
+}
+
+impl SstoreWalk<'_> {
+    fn struct_sstores(&mut self, st: &SolStruct, max_depth: usize) -> Result<u64, LayoutError> {
+        if self.path.contains(&st.name) {
+            return Ok(1);
+        }
+
+        if !self.locations.contains_key(&st.name) {
+            let locations = field_locations(st, self.all_structs)?;
+            self.locations.insert(st.name.clone(), locations);
+        }
+        let fields = self.locations[&st.name].clone();
+
+        self.path.push(st.name.clone());
+        let mut value_slots = BTreeSet::new();
+        let mut writes = 0;
+        for field in fields {
+            if field.typ.is_value_type() {
+                value_slots.insert(field.slot);
+            } else {
+                // an error ends the whole walk, so the path doesn't need unwinding
+                writes += self.type_sstores(&field.typ, max_depth)?;
+            }
+        }
+        self.path.pop();
+
+        Ok(value_slots.len() as u64 + writes)
+    }
+
+    /// SSTOREs to write a single value of `typ` on its own, see [`sstore_count`].
+    fn type_sstores(&mut self, typ: &SolType, max_depth: usize) -> Result<u64, LayoutError> {
+        if max_depth == 0 {
+            return Err(LayoutError::TooDeep(truncate_type(&typ.to_string())));
+        }
+
+        Ok(match typ {
+            SolType::Custom(st) => self.struct_sstores(st, max_depth - 1)?,
+            SolType::Custom2(st_name) => {
+                let st = self
+                    .all_structs
+                    .get(st_name)
+                    .ok_or_else(|| LayoutError::UnknownStruct(st_name.clone()))?;
+                self.struct_sstores(st, max_depth - 1)?
+            }
+            // packed like the fields of a struct, so that's just the slots it spans
+            SolType::FixedArray(inner, _) if inner.is_value_type() => {
+                snap_to_upper_256(typ.size(self.all_structs)?) / 256
+            }
+            SolType::FixedArray(inner, len) => len
+                .checked_mul(self.type_sstores(inner, max_depth - 1)?)
+                .ok_or(LayoutError::ArrayTooLarge)?,
+            SolType::Mapping(_, value) => self.type_sstores(value, max_depth - 1)?,
+            SolType::Array(inner) => 1 + self.type_sstores(inner, max_depth - 1)?,
+            _ => 1,
+        })
+    }
+}
+
+/// Where a field of a struct ends up, relative to the first slot of the struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLocation {
+    pub name: String,
+    pub typ: SolType,
+    pub slot: u64,
+    /// Byte offset within `slot`.
+    pub offset: u64,
+    /// Bits taken up by the field, including the padding inside of structs and arrays.
+    pub bits: u64,
+    pub doc: Option<String>,
+}
+
+/// Lays out the fields of `st` one after the other.
+pub fn field_locations(
+    st: &SolStruct,
+    all_structs: &BTreeMap<String, SolStruct>,
+) -> Result<Vec<FieldLocation>, LayoutError> {
+    SolType::Custom(st.clone()).check_cycles(all_structs, &mut vec![], DEFAULT_MAX_DEPTH)?;
+
+    let mut locations = vec![];
+    let mut size = 0;
+    let mut current_word_bits_allocated = 0;
+    for (name, typ, doc) in &st.fields {
+        let before = size;
+        update_state(
+            typ,
+            &mut current_word_bits_allocated,
+            &mut size,
+            all_structs,
+            DEFAULT_MAX_DEPTH,
+        )?;
+
+        // value types end where they got allocated, everything else starts on a fresh slot
+        let start = if typ.forces_new_slot() {
+            snap_to_upper_256(before)
+        } else {
+            size - typ.size(all_structs)?
+        };
+
+        // solidity never splits a value type across two slots, so this is a bug in the packing
+        if typ.is_value_type() && start % 256 + (size - start) > 256 {
+            return Err(LayoutError::SplitAcrossSlots(format!("{}.{name}", st.name)));
+        }
+
+        locations.push(FieldLocation {
+            name: name.clone(),
+            typ: typ.clone(),
+            slot: start / 256,
+            offset: start % 256 / 8,
+            bits: size - start,
+            doc: doc.clone(),
+        });
+    }
+
+    Ok(locations)
+}
+
+/// The layout of a struct along with the layouts of the structs it embeds, to find where nested
+/// fields end up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageLayout {
+    pub struct_name: String,
+    pub fields: Vec<FieldLocation>,
+    /// Layouts of the struct typed fields, by field name.
+    pub nested: BTreeMap<String, StorageLayout>,
+}
+
+impl StorageLayout {
+    pub fn new(
+        st: &SolStruct,
+        all_structs: &BTreeMap<String, SolStruct>,
+    ) -> Result<Self, LayoutError> {
+        let fields = field_locations(st, all_structs)?;
+
+        let mut nested = BTreeMap::new();
+        for field in &fields {
+            let inner = match &field.typ {
+                SolType::Custom(inner) => inner,
+                SolType::Custom2(st_name) => all_structs
+                    .get(st_name)
+                    .ok_or_else(|| LayoutError::UnknownStruct(st_name.clone()))?,
+                _ => continue,
+            };
+            nested.insert(field.name.clone(), Self::new(inner, all_structs)?);
+        }
+
+        Ok(Self {
+            struct_name: st.name.clone(),
+            fields,
+            nested,
+        })
+    }
+
+    /// Slot and byte offset of the field at `path` relative to the first slot of this struct,
+    /// `["inner", "a"]` for `s.inner.a`.
+    ///
+    /// Embedded structs always start on a fresh slot, so a nested field sits at its offset within
+    /// the embedded struct, and the slot of the embedded struct plus its slot within it.
+    pub fn offset_of(&self, path: &[&str]) -> Option<(u64, u8)> {
+        let (name, rest) = path.split_first()?;
+        let field = self.fields.iter().find(|field| field.name == *name)?;
+        if rest.is_empty() {
+            return Some((field.slot, field.offset as u8));
+        }
+
+        let (slot, offset) = self.nested.get(*name)?.offset_of(rest)?;
+        Some((field.slot + slot, offset))
+    }
+
+    /// Slot the top level field `field` starts in, relative to the first slot of this struct.
+    pub fn slot_of(&self, field: &str) -> Option<u64> {
+        self.offset_of(&[field]).map(|(slot, _)| slot)
+    }
+
+    /// Byte offset of the top level field `field` within the slot it starts in.
+    pub fn byte_offset_of(&self, field: &str) -> Option<u8> {
+        self.offset_of(&[field]).map(|(_, offset)| offset)
+    }
+
+    /// The fields grouped by the slot they start in, in slot order. Slots taken up by a field
+    /// that started in an earlier one, or left empty, are skipped.
+    pub fn slots(&self) -> impl Iterator<Item = SlotView<'_>> {
+        self.fields
+            .chunk_by(|a, b| a.slot == b.slot)
+            .map(|entries| SlotView {
+                index: entries[0].slot,
+                entries,
+            })
+    }
+}
+
+/// The fields starting in one slot of a [`StorageLayout`], from [`StorageLayout::slots`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotView<'a> {
+    pub index: u64,
+    pub entries: &'a [FieldLocation],
+}
+
+/// Reorders the fields of `st` into the minimum number of slots.
+///
+/// Value type fields are bin-packed first-fit-decreasing into 256 bit slots. Every other field
+/// anchors on a fresh slot anyway, so those keep their relative order and follow the packed
+/// value types.
+pub fn optimize_struct(
+    st: &SolStruct,
+    all_structs: &BTreeMap<String, SolStruct>,
+) -> Result<SolStruct, LayoutError> {
+    let mut value_fields = vec![];
+    let mut anchored_fields = vec![];
+    for field in &st.fields {
+        if field.1.is_value_type() {
+            value_fields.push((field.1.size(all_structs)?, field.clone()));
+        } else {
+            anchored_fields.push(field.clone());
+        }
+    }
+
+    // stable sort, so equally sized fields keep their declaration order
+    value_fields.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut slots: Vec<(u64, Vec<_>)> = vec![];
+    for (bits, field) in value_fields {
+        match slots.iter_mut().find(|(used, _)| used + bits <= 256) {
+            Some((used, fields)) => {
+                *used += bits;
+                fields.push(field);
+            }
+            None => slots.push((bits, vec![field])),
+        }
+    }
+
+    let fields = slots
+        .into_iter()
+        .flat_map(|(_, fields)| fields)
+        .chain(anchored_fields)
+        .collect();
+
+    Ok(SolStruct {
+        name: st.name.clone(),
+        fields,
+        _inner: st._inner.clone(),
+    })
+}
+
+/// The computed storage layout of a single struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutReport {
+    pub struct_name: String,
+    pub total_slots: u64,
+    /// Bits used, before rounding up to whole slots.
+    pub total_bits: u64,
+    pub fields: Vec<FieldLocation>,
+}
+
+/// The most slots [`LayoutReport::byte_occupancy`] maps, a huge fixed array would take up
+/// more memory than there is.
+pub const MAX_MAPPED_SLOTS: u64 = 1 << 16;
+
+/// Marks the bytes taken up by a `typ` starting at the first of `slots` as `field`. The
+/// elements of value type fixed arrays are packed, anything else that isn't a value type is
+/// shown as taking up its slots whole.
+fn mark_slots(slots: &mut [[Option<usize>; 32]], typ: &SolType, field: usize) {
+    match typ {
+        SolType::FixedArray(inner, len) if inner.is_value_type() => {
+            let element_bytes = inner.size(&BTreeMap::new()).map_or(32, |bits| bits / 8);
+            let per_slot = 32 / element_bytes;
+            let mut left = *len;
+            for slot in slots {
+                let elements = left.min(per_slot);
+                slot[..(elements * element_bytes) as usize].fill(Some(field));
+                left -= elements;
+            }
+        }
+        SolType::FixedArray(inner, len) if *len > 0 => {
+            let element_slots = slots.len() / *len as usize;
+            for element in slots.chunks_mut(element_slots.max(1)) {
+                mark_slots(element, inner, field);
+            }
+        }
+        _ => {
+            for slot in slots {
+                slot.fill(Some(field));
+            }
+        }
+    }
+}
+
+impl LayoutReport {
+    /// For every slot of the struct, which field each of its 32 bytes belongs to as an index into
+    /// `fields`, `None` for padding.
+    ///
+    /// Anything that isn't a value type or a fixed array is shown as taking up its slots whole.
+    /// Fails on structs taking up more than [`MAX_MAPPED_SLOTS`].
+    pub fn byte_occupancy(&self) -> Result<Vec<[Option<usize>; 32]>, LayoutError> {
+        if self.total_slots > MAX_MAPPED_SLOTS {
+            return Err(LayoutError::TooManySlots(self.total_slots));
+        }
+
+        let mut slots = vec![[None; 32]; self.total_slots as usize];
+        for (i, field) in self.fields.iter().enumerate() {
+            if field.typ.is_value_type() {
+                let start = field.offset as usize;
+                let end = (start + field.bits.div_ceil(8) as usize).min(32);
+                slots[field.slot as usize][start..end].fill(Some(i));
+            } else {
+                let start = field.slot as usize;
+                let end = start + (field.offset * 8 + field.bits).div_ceil(256) as usize;
+                mark_slots(
+                    &mut slots[start..end.min(self.total_slots as usize)],
+                    &field.typ,
+                    i,
+                );
+            }
+        }
+
+        Ok(slots)
+    }
+
+    /// Bytes left unused at the end of the last slot, room for more fields that wouldn't take
+    /// up another slot.
+    pub fn free_bytes(&self) -> u64 {
+        (snap_to_upper_256(self.total_bits) - self.total_bits) / 8
+    }
+
+    /// The unused byte ranges of every slot, in slot order, where a field that small could
+    /// still go without taking up another slot.
+    pub fn free_ranges(&self) -> Result<Vec<(u64, std::ops::Range<usize>)>, LayoutError> {
+        let mut ranges = vec![];
+        for (slot, bytes) in self.byte_occupancy()?.iter().enumerate() {
+            let mut start = None;
+            for (i, byte) in bytes.iter().enumerate() {
+                match (byte, start) {
+                    (None, None) => start = Some(i),
+                    (Some(_), Some(free)) => {
+                        ranges.push((slot as u64, free..i));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(free) = start {
+                ranges.push((slot as u64, free..bytes.len()));
+            }
+        }
+
+        Ok(ranges)
+    }
+
+    /// The fields grouped by the slot they start in, in slot order. Fields taking up several
+    /// slots are only listed under their first one.
+    pub fn fields_by_slot(&self) -> BTreeMap<u64, Vec<&FieldLocation>> {
+        let mut slots = BTreeMap::<_, Vec<_>>::new();
+        for field in &self.fields {
+            slots.entry(field.slot).or_default().push(field);
+        }
+
+        slots
+    }
+}
+
+/// Renders the struct name, a table of every field with its slot, offset and type, and a
+/// `name: slots [bits]` summary line.
+///
+/// The alternate form (`{:#}`) prints the slots in hex, ready to paste into `eth_getStorageAt`.
+impl fmt::Display for LayoutReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .fields
+            .iter()
+            .map(|field| {
+                [
+                    if f.alternate() {
+                        format!("{:#x}", field.slot)
+                    } else {
+                        field.slot.to_string()
+                    },
+                    field.offset.to_string(),
+                    // the column is as wide as its widest type
+                    truncate_type(&field.typ.to_string()),
+                    field.name.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = ["slot", "offset", "type", "name"];
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        writeln!(f, "{}:\n-------", self.struct_name)?;
+        for row in std::iter::once(header.map(String::from)).chain(rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        write!(
+            f,
+            "{}: {} [{}]",
+            self.struct_name, self.total_slots, self.total_bits
+        )
+    }
+}
+
+static CONSTANT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\bconstant\b[^=;]*?\b(?<name>\w+)\s*=\s*(?<value>[^;]+);")
+        .expect("valid constant regex")
+});
+
+/// The integer constants declared anywhere in `src`, by name, to size arrays like
+/// `uint256[N]` with. Constants that aren't integer expressions, like hashes, are left out.
+pub(crate) fn constants(src: &str) -> BTreeMap<String, u64> {
+    let mut constants = BTreeMap::new();
+    for captures in CONSTANT_REGEX.captures_iter(src) {
+        // a constant can refer to the ones declared before it
+        if let Ok(value) = LengthExpr::eval(&captures["value"], &constants) {
+            constants.insert(captures["name"].to_string(), value);
+        }
+    }
+
+    constants
+}
+
+static ENUM_HEADER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\benum\s+(?<name>\w+)\s*\{").expect("valid enum header regex"));
+
+static CONTRACT_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:contract|interface)\s+(?<name>\w+)").expect("valid contract name regex")
+});
+
+static VALUE_TYPE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\btype\s+(?<name>\w+)\s+is\s+(?<underlying>[^;]+);")
+        .expect("valid value type regex")
+});
+
+/// The names declared in `src` that stand for a value type rather than a struct, with the type
+/// they are stored as: enums take a single byte as solc caps them at 256 members, contracts and
+/// interfaces are stored as their address, and user defined value types, `type Price is
+/// uint128;`, as their underlying type.
+pub(crate) fn type_aliases(src: &str) -> BTreeMap<String, SolType> {
+    let code = contract::strip_comments(src);
+    let contracts = CONTRACT_NAME_REGEX
+        .captures_iter(&code)
+        .map(|captures| (captures["name"].to_string(), SolType::Address));
+    let enums = ENUM_HEADER_REGEX
+        .captures_iter(src)
+        .map(|captures| (captures["name"].to_string(), SolType::Uint(8)));
+    // solc only allows elementary value types underneath
+    let value_types = VALUE_TYPE_REGEX
+        .captures_iter(&code)
+        .filter_map(|captures| {
+            let underlying = captures["underlying"].parse::<SolType>().ok()?;
+            underlying
+                .is_value_type()
+                .then(|| (captures["name"].to_string(), underlying))
+        });
+
+    contracts.chain(enums).chain(value_types).collect()
+}
+
+/// The names of every struct declared in `src`, qualified by the contract, library or
+/// interface they are declared in, like `Vault.Order`.
+pub(crate) fn struct_names(src: &str) -> BTreeSet<String> {
+    scan_structs(src)
+        .into_iter()
+        .map(|chunk| match chunk.scope {
+            Some(scope) => format!("{scope}.{}", chunk.name),
+            None => chunk.name,
+        })
+        .collect()
+}
+
+/// `Name` out of `Scope.Name`.
+fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Qualifies the struct names in `typ`, referred to from `scope`, to the struct they mean
+/// among `names`: the one declared in the same scope, else the one at the top level of the
+/// file, else the only one anywhere by that name. Names of structs that don't exist are kept
+/// as they are.
+///
+/// Qualified names like `Lib.Order` are kept too, unless no struct is declared in `Lib` by
+/// that name but one is at the top level, as when `Lib` itself isn't part of the input.
+///
+/// Fails on mappings and arrays nested more than `max_depth` levels deep.
+pub(crate) fn qualify(
+    typ: SolType,
+    scope: Option<&str>,
+    names: &BTreeSet<String>,
+    max_depth: usize,
+) -> Result<SolType, LayoutError> {
+    if max_depth == 0 {
+        return Err(LayoutError::TooDeep(truncate_type(&typ.to_string())));
+    }
+
+    Ok(match typ {
+        SolType::Custom2(name) if !name.contains('.') => {
+            if let Some(scoped) = scope
+                .map(|scope| format!("{scope}.{name}"))
+                .filter(|scoped| names.contains(scoped))
+            {
+                return Ok(SolType::Custom2(scoped));
+            }
+            if names.contains(&name) {
+                return Ok(SolType::Custom2(name));
+            }
+
+            let candidates = names
+                .iter()
+                .filter(|candidate| candidate.contains('.') && unqualified(candidate) == name)
+                .cloned()
+                .collect::<Vec<_>>();
+            match &candidates[..] {
+                [] => SolType::Custom2(name),
+                [candidate] => SolType::Custom2(candidate.clone()),
+                _ => return Err(LayoutError::AmbiguousStruct { name, candidates }),
+            }
+        }
+        SolType::Custom2(name) if !names.contains(&name) && names.contains(unqualified(&name)) => {
+            SolType::Custom2(unqualified(&name).to_string())
+        }
+        SolType::Mapping(key, value) => SolType::Mapping(
+            Box::new(qualify(*key, scope, names, max_depth - 1)?),
+            Box::new(qualify(*value, scope, names, max_depth - 1)?),
+        ),
+        SolType::Array(inner) => {
+            SolType::Array(Box::new(qualify(*inner, scope, names, max_depth - 1)?))
+        }
+        SolType::FixedArray(inner, len) => {
+            SolType::FixedArray(Box::new(qualify(*inner, scope, names, max_depth - 1)?), len)
+        }
+        typ => typ,
+    })
+}
+
+/// Replaces the names of `aliases` in `typ` with the type they are stored as, see
+/// [`type_aliases`]. Fails on mappings and arrays nested more than `max_depth` levels deep.
+pub(crate) fn lower_aliases(
+    typ: SolType,
+    aliases: &BTreeMap<String, SolType>,
+    max_depth: usize,
+) -> Result<SolType, LayoutError> {
+    if max_depth == 0 {
+        return Err(LayoutError::TooDeep(truncate_type(&typ.to_string())));
+    }
+
+    Ok(match typ {
+        SolType::Custom2(name) if aliases.contains_key(unqualified(&name)) => {
+            aliases[unqualified(&name)].clone()
+        }
+        SolType::Mapping(key, value) => SolType::Mapping(
+            Box::new(lower_aliases(*key, aliases, max_depth - 1)?),
+            Box::new(lower_aliases(*value, aliases, max_depth - 1)?),
+        ),
+        SolType::Array(inner) => {
+            SolType::Array(Box::new(lower_aliases(*inner, aliases, max_depth - 1)?))
+        }
+        SolType::FixedArray(inner, len) => SolType::FixedArray(
+            Box::new(lower_aliases(*inner, aliases, max_depth - 1)?),
+            len,
+        ),
+        typ => typ,
+    })
+}
+
+/// Parses every struct definition in the Solidity source `src`, keyed by struct name.
+///
+/// Structs declared in a contract, library or interface are named after it, `Vault.Order`, so
+/// ones in different contracts can share a name. Fields refer to the struct of their own
+/// contract first, then to the one at the top level of the file, then to the only one by that
+/// name anywhere, and are an error if there are several.
+///
+/// Fields typed by an enum declared in `src` are lowered to `uint8`, ones typed by a contract or
+/// interface to `address`, ones typed by a user defined value type to its underlying type, and
+/// the lengths of fixed arrays can be expressions over the integer constants declared in `src`.
+pub fn parse_structs(src: &str) -> Result<BTreeMap<String, SolStruct>, LayoutError> {
+    parse_structs_with(src, false)
+}
+
+/// Like [`parse_structs`], but a field typed by a name that isn't a struct, enum, contract,
+/// interface or user defined value type declared in `src` is an error pointing at its line,
+/// rather than an unknown struct once it gets sized. Catches typos like `unit256`.
+pub fn parse_structs_strict(src: &str) -> Result<BTreeMap<String, SolStruct>, LayoutError> {
+    parse_structs_with(src, true)
+}
+
+fn parse_structs_with(src: &str, strict: bool) -> Result<BTreeMap<String, SolStruct>, LayoutError> {
+    let aliases = type_aliases(src);
+    let constants = constants(src);
+    let chunks = scan_structs(src);
+    let names = struct_names(src);
+    let known = strict.then(|| {
+        names
+            .iter()
+            .flat_map(|name| [name.clone(), unqualified(name).to_string()])
+            .chain(aliases.keys().cloned())
+            .collect::<BTreeSet<_>>()
+    });
+
+    let mut structs = BTreeMap::new();
+    // the line every struct is declared on, to point at both of a duplicate
+    let mut lines = BTreeMap::new();
+    for StructChunk {
+        line, scope, src, ..
+    } in chunks
+    {
+        let mut st = parse_struct_with(&src, line, &constants, known.as_ref())?;
+        if let Some(scope) = &scope {
+            st.name = format!("{scope}.{}", st.name);
+        }
+        st.fields = st
+            .fields
+            .into_iter()
+            .map(|(name, typ, doc)| {
+                let in_field = |e| LayoutError::Field {
+                    owner: st.name.clone(),
+                    field: name.clone(),
+                    source: Box::new(e),
+                };
+                let typ = qualify(typ, scope.as_deref(), &names, DEFAULT_MAX_DEPTH)
+                    .and_then(|typ| lower_aliases(typ, &aliases, DEFAULT_MAX_DEPTH))
+                    .map_err(in_field)?;
+                Ok((name, typ, doc))
+            })
+            .collect::<Result<_, LayoutError>>()?;
+
+        if let Some(first) = lines.insert(st.name.clone(), line) {
+            return Err(LayoutError::DuplicateStruct {
+                name: st.name,
+                lines: [first, line],
+            });
+        }
+        structs.insert(st.name.clone(), st);
+    }
+
+    Ok(structs)
+}
+
+/// Lays out `st`, resolving the structs it embeds from `structs`.
+pub fn layout_report(
+    st: &SolStruct,
+    structs: &BTreeMap<String, SolStruct>,
+) -> Result<LayoutReport, LayoutError> {
+    let total_bits = SolType::Custom(st.clone()).size(structs)?;
+    Ok(LayoutReport {
+        struct_name: st.name.clone(),
+        total_slots: snap_to_upper_256(total_bits) / 256,
+        total_bits,
+        fields: field_locations(st, structs)?,
+    })
+}
+
+/// Lays out `reports` one after the other as a single layout named `name`, the way they would
+/// end up as consecutive fields of one struct: every one starts on a fresh slot right after the
+/// last slot of the one before. Fields are renamed to `Struct.field`.
+pub fn concat_reports(name: &str, reports: &[LayoutReport]) -> LayoutReport {
+    let mut fields = vec![];
+    let mut total_slots = 0;
+    let mut total_bits = 0;
+    for report in reports {
+        fields.extend(report.fields.iter().map(|field| FieldLocation {
+            name: format!("{}.{}", report.struct_name, field.name),
+            slot: total_slots + field.slot,
+            ..field.clone()
+        }));
+        total_bits = total_slots * 256 + report.total_bits;
+        total_slots += report.total_slots;
+    }
+
+    LayoutReport {
+        struct_name: name.to_string(),
+        total_slots,
+        total_bits,
+        fields,
+    }
+}
+
+/// The struct stored inline by a field of type `typ`, directly or as the elements of a fixed
+/// array. Structs behind a mapping or a dynamic array are stored elsewhere.
+pub(crate) fn embedded_struct(typ: &SolType) -> Option<&str> {
+    match typ {
+        SolType::Custom(st) => Some(&st.name),
+        SolType::Custom2(name) => Some(name),
+        SolType::FixedArray(inner, _) => embedded_struct(inner),
+        _ => None,
+    }
+}
+
+/// Adds the name of every struct `typ` refers to to `names`, wherever it's stored.
+fn referenced_structs<'a>(typ: &'a SolType, names: &mut BTreeSet<&'a str>) {
+    match typ {
+        SolType::Custom(st) => {
+            names.insert(&st.name);
+        }
+        SolType::Custom2(name) => {
+            names.insert(name);
+        }
+        SolType::Mapping(key, value) => {
+            referenced_structs(key, names);
+            referenced_structs(value, names);
+        }
+        SolType::Array(inner) | SolType::FixedArray(inner, _) => referenced_structs(inner, names),
+        _ => {}
+    }
+}
+
+/// The structs no other struct refers to, in name order. Laid out one after the other they add
+/// up to the whole storage of `structs` without counting a struct twice, whether it's embedded
+/// in another one or stored behind its mappings and dynamic arrays.
+pub fn top_level_structs(structs: &BTreeMap<String, SolStruct>) -> Vec<&SolStruct> {
+    let mut referenced = BTreeSet::new();
+    for st in structs.values() {
+        let mut names = BTreeSet::new();
+        for (_, typ, _) in &st.fields {
+            referenced_structs(typ, &mut names);
+        }
+        // a struct can refer to itself through a mapping, `mapping(uint => Node) children`
+        names.remove(st.name.as_str());
+        referenced.extend(names);
+    }
+
+    structs
+        .values()
+        .filter(|st| !referenced.contains(st.name.as_str()))
+        .collect()
+}
+
+/// Lays out every struct in `structs`, in name order.
+pub fn layout_reports(
+    structs: &BTreeMap<String, SolStruct>,
+) -> Result<Vec<LayoutReport>, LayoutError> {
+    structs
+        .values()
+        .map(|st| layout_report(st, structs))
+        .collect()
+}
+
+/// Computes the layout of every struct defined in the Solidity source `src`.
+pub fn compute_layout(src: &str) -> Result<Vec<LayoutReport>, LayoutError> {
+    layout_reports(&parse_structs(src)?)
+}
@@ -1,320 +1,380 @@
 use std::{
     collections::BTreeMap,
-    io::{self, BufRead, BufReader},
-    str::FromStr,
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, BufReader, IsTerminal},
+    path::{Path, PathBuf},
 };
 
+use clap::{Parser, Subcommand, ValueEnum};
 use eyre::OptionExt;
-use regex::Regex;
-
-#[derive(Debug, Clone)]
-enum SolType {
-    Uint(u16),
-    Int(u16),
-    Address,
-    Bool,
-    Bytes(u8),
-    BytesArbitrary,
-    Custom(SolStruct),
-    Custom2(String),
-    #[allow(dead_code)]
-    Mapping(Box<SolType>, Box<SolType>),
-    #[allow(dead_code)]
-    Array(Box<SolType>),
-    FixedArray(Box<SolType>, u64),
-}
-
-fn snap_to_upper_256(size: u64) -> u64 {
-    let over = size % 256;
-    let size = if over == 0 { size } else { size + 256 - over };
-    assert!(size.is_multiple_of(256));
+use struct_storage_layout::{
+    LayoutError, LayoutReport, SolStruct, SolType, ast, concat_reports, contract, csv, diff, dot,
+    fields, forge, json, layout_report, layout_reports, optimize_struct, parse_structs,
+    parse_structs_strict, slot, slot_count, snap_to_upper_256, solc, sstore_count,
+    top_level_structs,
+};
 
-    size
+/// Computes the storage layout of Solidity structs, from source piped in on stdin or read from files.
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Without a subcommand the layouts are printed, same as `layout`.
+    #[command(flatten)]
+    layout: LayoutArgs,
 }
 
-// Storage layout rules: https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html
-//
-// - The first item in a storage slot is stored lower-order aligned.
-// - Value types use only as many bytes as are necessary to store them.
-// - If a value type does not fit the remaining part of a storage slot, it is stored in the next storage slot.
-// - Structs and array data always start a new slot and their items are packed tightly according to these rules.
-// - Items following struct or array data always start a new storage slot.
-impl SolType {
-    fn size(&self, all_structs: &BTreeMap<String, SolStruct>) -> eyre::Result<u64> {
-        Ok(match self {
-            Self::Uint(size) => (*size).into(),
-            Self::Int(size) => (*size).into(),
-            Self::Address => (20u32 * 8).into(),
-            Self::Bool => 1,
-            Self::Bytes(size) => *size as u64 * 8,
-            Self::BytesArbitrary => 256,
-            Self::Custom(sol_struct) => {
-                let mut size = 0;
-                let mut current_word_bits_allocated = 0;
-
-                fn update_state(
-                    typ: &SolType,
-                    current_word_bits_allocated: &mut u64,
-                    size: &mut u64,
-                    all_structs: &BTreeMap<String, SolStruct>,
-                ) -> eyre::Result<()> {
-                    let remainder_bits = 256 - *current_word_bits_allocated;
-
-                    match typ {
-                        // Value types use up only as many bytes as necessary if available, or
-                        // start on new slot if not enough space.
-                        SolType::Uint(_)
-                        | SolType::Int(_)
-                        | SolType::Address
-                        | SolType::Bool
-                        | SolType::Bytes(_) => {
-                            let bits_needed = typ.size(all_structs)?;
-                            if bits_needed <= remainder_bits {
-                                *current_word_bits_allocated += bits_needed;
-                                *size += bits_needed;
-                            } else {
-                                // move to next slot
-                                *current_word_bits_allocated = 0;
-                                *size += remainder_bits;
-                                // allocate bits in next slot
-                                *size += bits_needed;
-                                *current_word_bits_allocated += bits_needed;
-                            }
-                        }
-                        // Fixed array types are inlined
-                        SolType::FixedArray(sol_type, len) => {
-                            // move to next slot
-                            *current_word_bits_allocated = 0;
-                            *size = snap_to_upper_256(*size);
-
-                            for _ in 0..*len {
-                                update_state(
-                                    sol_type,
-                                    current_word_bits_allocated,
-                                    size,
-                                    all_structs,
-                                )?;
-                            }
-                        }
-                        // Mapping, Dynamic size array, arbitrary bytes, all take up the next full
-                        // slot.
-                        SolType::Mapping(_, _) | SolType::Array(_) | SolType::BytesArbitrary => {
-                            *current_word_bits_allocated = 0;
-                            *size = snap_to_upper_256(*size);
-                            *size += 256;
-                        }
-                        // Structs are packed tightly according to the rules above.
-                        // And they always start on a new slot.
-                        // Items following structs always start on a new slot
-                        SolType::Custom(_) => {
-                            *current_word_bits_allocated = 0;
-                            *size = snap_to_upper_256(*size);
-                            *size += typ.size(all_structs)?;
-                            *size = snap_to_upper_256(*size);
-                        }
-                        SolType::Custom2(st_name) => {
-                            let typ = SolType::Custom(
-                                all_structs
-                                    .get(st_name)
-                                    .ok_or_eyre(format!("struct not found: {st_name}"))?
-                                    .clone(),
-                            );
-                            update_state(&typ, current_word_bits_allocated, size, all_structs)?;
-                        }
-                    }
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the layout of every struct, field by field.
+    Layout(LayoutArgs),
+    /// Only print how many slots every struct takes up.
+    Size(InputArgs),
+    /// Check that the structs and contracts in NEW keep the storage layout of the ones in OLD.
+    Diff {
+        /// Solidity source before the upgrade.
+        old: PathBuf,
+        /// Solidity source after the upgrade.
+        new: PathBuf,
+    },
+}
 
-                    Ok(())
-                }
+/// Where the structs are read from, Solidity source on stdin unless one of the files is given.
+#[derive(Debug, clap::Args)]
+struct InputArgs {
+    /// Read the Solidity source from this file instead of stdin, or from every `.sol` file under
+    /// it when it's a directory.
+    #[arg(conflicts_with_all = ["from_ast", "from_json", "inline"])]
+    path: Option<PathBuf>,
+    /// Read struct definitions from solc's compact AST json instead of Solidity source on stdin.
+    #[arg(long, value_name = "FILE", conflicts_with = "from_json")]
+    from_ast: Option<PathBuf>,
+    /// Read a single struct from a json array of `{"name", "type"}` fields, named after the file.
+    #[arg(long, value_name = "FILE")]
+    from_json: Option<PathBuf>,
+    /// Also lay out the state variables of every contract in the source.
+    #[arg(long, conflicts_with_all = ["from_ast", "from_json"])]
+    contracts: bool,
+    /// Take the Solidity source from this argument instead of stdin, like
+    /// `--inline "struct S { uint128 a; uint128 b; }"`.
+    #[arg(long, value_name = "SRC", conflicts_with_all = ["from_ast", "from_json"])]
+    inline: Option<String>,
+    /// Fail on a field whose type isn't a builtin or a struct or enum declared in the source,
+    /// instead of taking it for a struct defined elsewhere.
+    #[arg(long, conflicts_with_all = ["from_ast", "from_json"])]
+    strict: bool,
+    /// Fail on features of the source this solc release doesn't have yet, like `transient`
+    /// before 0.8.28, instead of laying them out anyway.
+    #[arg(long, value_name = "X.Y.Z", conflicts_with_all = ["from_ast", "from_json"])]
+    solc_version: Option<solc::SolcVersion>,
+}
 
-                for (_, typ) in &sol_struct.fields {
-                    update_state(
-                        typ,
-                        &mut current_word_bits_allocated,
-                        &mut size,
-                        all_structs,
-                    )?;
-                }
+#[derive(Debug, clap::Args)]
+struct LayoutArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Also print the field order that needs the fewest slots.
+    #[arg(long)]
+    optimize: bool,
+    /// Check the computed layouts against `forge inspect <Contract> storageLayout --json` output.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["json", "markdown"])]
+    compare_forge: Option<PathBuf>,
+    /// Write the output to this file instead of stdout.
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+    /// Estimate the gas to write and read a whole struct.
+    #[arg(long)]
+    gas: bool,
+    /// Also print the slots all structs take up together, only counting the structs no other
+    /// struct refers to so nested ones aren't counted twice.
+    #[arg(long)]
+    aggregate: bool,
+    /// Warn about every struct that takes up more than this many slots, and fail.
+    #[arg(long, value_name = "N")]
+    max_slots: Option<u64>,
+    /// Count the SSTOREs it takes to write every field of a struct once.
+    #[arg(long)]
+    writes: bool,
+    /// Gas to write a slot, for `--gas`.
+    #[arg(long, value_name = "GAS")]
+    sstore_gas: Option<u64>,
+    /// Gas to read a slot, for `--gas`.
+    #[arg(long, value_name = "GAS")]
+    sload_gas: Option<u64>,
+    /// Print slot indices as `0x..` instead of decimal.
+    #[arg(long)]
+    hex_slots: bool,
+    /// Only print the bytes used by each struct in its summary line, not the slots.
+    #[arg(long, conflicts_with = "slots")]
+    bytes: bool,
+    /// Only print the slots each struct takes up in its summary line, not the bits.
+    #[arg(long)]
+    slots: bool,
+    /// Draw how the fields are packed into each slot.
+    #[arg(long)]
+    diagram: bool,
+    /// List the fields sharing each slot, like `slot 0: [a: uint128, b: uint128]`.
+    #[arg(long)]
+    by_slot: bool,
+    /// Print every slot byte range by byte range, with the field or padding stored there.
+    #[arg(long)]
+    table: bool,
+    /// List the unused byte ranges of every slot, like `slot 1: free [20..32)`.
+    #[arg(long)]
+    free: bool,
+    /// Never color the diagram, it is only colored when writing to a terminal anyway.
+    #[arg(long)]
+    no_color: bool,
+    /// Print the slot of a mapping entry, like `balances[0xabc..]`, instead of the layouts.
+    #[arg(long, value_name = "EXPR")]
+    slot_of: Option<String>,
+    /// Only print the slot a top level field starts at, like `balances` or `Vault.balances`.
+    #[arg(long, value_name = "FIELD", conflicts_with = "slot_of")]
+    starts_at: Option<String>,
+    /// Lay out the top level structs one after the other instead, with continuing slot numbers,
+    /// as if they were consecutive fields of one contract.
+    #[arg(long, conflicts_with_all = ["format", "json", "markdown"])]
+    continuous: bool,
+    /// Only print whether every struct already takes up as few slots as its best field order,
+    /// `OPTIMAL` or `SUBOPTIMAL (N slots, could be M)`, and fail if any doesn't.
+    #[arg(
+        long,
+        conflicts_with_all = ["format", "json", "markdown", "continuous", "slot_of", "starts_at"]
+    )]
+    check_optimal: bool,
+    /// How to print the layouts. The text output takes the extras like `--diagram` and `--gas`,
+    /// the others only print the layouts.
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["json", "markdown"])]
+    format: Format,
+    /// Short for `--format json`.
+    #[arg(long, conflicts_with = "markdown")]
+    json: bool,
+    /// Short for `--format md`.
+    #[arg(long)]
+    markdown: bool,
+    /// Read one type or struct definition at a time and print its size right away, until an
+    /// empty line or `:quit`. Structs stay defined for the lines after them.
+    #[arg(
+        long,
+        conflicts_with_all = ["from_ast", "from_json", "contracts", "inline", "solc_version", "out"]
+    )]
+    interactive: bool,
+    /// Only print the size of this type, like `uint128` or `mapping(address => uint)`. The
+    /// structs it names are taken from `--inline`, stdin isn't read.
+    #[arg(
+        long = "type",
+        value_name = "TYPE",
+        conflicts_with_all = ["from_ast", "from_json", "contracts", "interactive", "solc_version"]
+    )]
+    typ: Option<String>,
+}
 
-                size
-            }
-            Self::Custom2(st_name) => {
-                let typ = Self::Custom(
-                    all_structs
-                        .get(st_name)
-                        .ok_or_eyre(format!("unknown struct: {st_name}"))?
-                        .clone(),
-                );
-                typ.size(all_structs)?
-            }
-            Self::Mapping(_, _) => 256,
-            Self::Array(_) => 256,
-            Self::FixedArray(sol_type, len) => {
-                let size = sol_type.size(all_structs)?;
-                let remainder = 256 - (size % 256);
-                let size = size + remainder;
-                assert!(size % 256 == 0);
-
-                size * len
-            }
-        })
-    }
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// The table of every struct, followed by the extras asked for.
+    #[default]
+    Text,
+    Json,
+    /// Every byte range of every slot, like `--table`.
+    Table,
+    Csv,
+    /// Markdown tables.
+    Md,
+    /// A graphviz graph of the structs and the ones they embed.
+    Dot,
+    /// Only the diagrams of `--diagram`.
+    Diagram,
 }
 
-const MAPPING_REGEX: &str =
-    r"\s*mapping\s*\(\s*(?<key_type>\w+)\s*=>\s*(?<value_type>\w+(?:\[\d*\])?)\s*\)";
-const FIXED_ARRAY_REGEX: &str = r"\s*(?<type>\w+)\s*\[\s*(?<size>\d+)\s*\]\s*";
-
-impl FromStr for SolType {
-    type Err = eyre::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s.trim() {
-            "uint" => Self::Uint(256),
-            "int" => Self::Int(256),
-            "address" => Self::Address,
-            "bool" => Self::Bool,
-            "bytes" => Self::BytesArbitrary,
-            "bytes1" | "bytes2" | "bytes3" | "bytes4" | "bytes5" | "bytes6" | "bytes7"
-            | "bytes8" | "bytes9" | "bytes10" | "bytes11" | "bytes12" | "bytes13" | "bytes14"
-            | "bytes15" | "bytes16" | "bytes17" | "bytes18" | "bytes19" | "bytes20" | "bytes21"
-            | "bytes22" | "bytes23" | "bytes24" | "bytes25" | "bytes26" | "bytes27" | "bytes28"
-            | "bytes29" | "bytes30" | "bytes31" | "bytes32" => {
-                Self::Bytes(s.replace("bytes", "").parse()?)
-            }
-            "uint8" | "uint16" | "uint24" | "uint32" | "uint40" | "uint48" | "uint56"
-            | "uint64" | "uint72" | "uint80" | "uint88" | "uint96" | "uint104" | "uint112"
-            | "uint120" | "uint128" | "uint136" | "uint144" | "uint152" | "uint160" | "uint168"
-            | "uint176" | "uint184" | "uint192" | "uint200" | "uint208" | "uint216" | "uint224"
-            | "uint232" | "uint240" | "uint248" | "uint256" => {
-                Self::Uint(s.replace("uint", "").parse()?)
-            }
-            "int8" | "int16" | "int24" | "int32" | "int40" | "int48" | "int56" | "int64"
-            | "int72" | "int80" | "int88" | "int96" | "int104" | "int112" | "int120" | "int128"
-            | "int136" | "int144" | "int152" | "int160" | "int168" | "int176" | "int184"
-            | "int192" | "int200" | "int208" | "int216" | "int224" | "int232" | "int240"
-            | "int248" | "int256" => Self::Int(s.replace("int", "").parse()?),
-            s if s.starts_with("mapping") => {
-                let captures = Regex::new(MAPPING_REGEX)
-                    .map_err(|e| eyre::eyre!("mapping regex instantiation error: {e}"))?
-                    .captures(s)
-                    .ok_or_eyre(format!("mapping didnt match: {s}"))?;
-                let key_type = &captures["key_type"];
-                let value_type = &captures["value_type"];
-
-                Self::Mapping(
-                    Box::new(
-                        (key_type.parse::<Self>())
-                            .map_err(|e| eyre::eyre!("error parsing {key_type} {e}"))?,
-                    ),
-                    Box::new(
-                        (value_type.parse::<Self>())
-                            .map_err(|e| eyre::eyre!("error parsing {value_type} {e}"))?,
-                    ),
-                )
-            }
-            s if s.ends_with("[]") => {
-                let inner_type = s.replace("[]", "").parse::<Self>()?;
-                Self::Array(Box::new(inner_type))
-            }
-            s if s.contains("[") && s.contains("]") => {
-                let captures = Regex::new(FIXED_ARRAY_REGEX)
-                    .map_err(|e| eyre::eyre!("fixed array regex instantiation error: {e}"))?
-                    .captures(s)
-                    .ok_or_eyre(format!("fixed array didnt match: {s}"))?;
-                let value_type = &captures["type"];
-                let size = &captures["size"];
-                let size = size
-                    .parse::<u64>()
-                    .map_err(|e| eyre::eyre!("error parsing {size} {e}"))?;
-                Self::FixedArray(
-                    Box::new(
-                        value_type
-                            .parse::<Self>()
-                            .map_err(|e| eyre::eyre!("error parsing {value_type} {e}"))?,
-                    ),
-                    size,
-                )
-            }
-            _ => Self::Custom2(s.to_string()),
-        })
+impl LayoutArgs {
+    /// `--format`, or the format picked by one of its shorthands.
+    fn format(&self) -> Format {
+        if self.json {
+            Format::Json
+        } else if self.markdown {
+            Format::Md
+        } else {
+            self.format
+        }
     }
-}
 
-#[derive(Debug, Clone)]
-struct SolStruct {
-    name: String,
-    fields: Vec<(String, SolType)>,
-    _inner: String,
+    /// The diagram is only colored when it ends up on a terminal.
+    fn color(&self) -> bool {
+        !self.no_color && self.out.is_none() && io::stdout().is_terminal()
+    }
 }
 
-fn chunk_structs(src: &str) -> eyre::Result<Vec<String>> {
-    let mut structs = vec![];
+type Structs = BTreeMap<String, SolStruct>;
 
-    let mut curr_struct = vec![];
-    for line in src.lines() {
-        if line.trim().is_empty() {
-            continue;
+impl InputArgs {
+    /// Reads the structs, and the contracts when asked for.
+    fn read(&self) -> eyre::Result<(Structs, Structs)> {
+        if let Some(path) = &self.from_ast {
+            return Ok((ast::parse_ast(&read_file(path)?)?, Default::default()));
+        }
+        if let Some(path) = &self.from_json {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_eyre(format!("can't name a struct after {}", path.display()))?;
+            let st = fields::parse_fields_json(name, &read_file(path)?)?;
+            return Ok((BTreeMap::from([(st.name.clone(), st)]), Default::default()));
         }
 
-        curr_struct.push(line.to_string());
-        if line.contains("}") {
-            structs.push(curr_struct.join("\n"));
-            curr_struct = vec![];
+        let src = match (&self.inline, &self.path) {
+            (Some(src), _) => src.clone(),
+            (None, Some(path)) if path.is_dir() => return self.read_dir(path),
+            (None, Some(path)) => read_file(path)?,
+            (None, None) => read_stdin(),
+        };
+        if let Some(version) = self.solc_version {
+            solc::check_features(&src, version)?;
         }
+        Ok(self.parse(&src)?)
     }
 
-    Ok(structs)
-}
-
-fn parse_struct(src: &str) -> eyre::Result<SolStruct> {
-    let mut struct_name = "";
-    let mut fields = vec![];
+    fn parse(&self, src: &str) -> Result<(Structs, Structs), LayoutError> {
+        let contracts = if self.contracts {
+            contract::parse_contracts(src)?
+        } else {
+            Default::default()
+        };
+        let structs = if self.strict {
+            parse_structs_strict(src)?
+        } else {
+            parse_structs(src)?
+        };
+        Ok((structs, contracts))
+    }
 
-    for line in src.lines() {
-        if line.trim().is_empty() {
-            continue;
+    /// Every `.sol` file under `dir` parsed as one source, so structs can refer to the ones of
+    /// other files. Files that don't read or parse are reported by name and line and skipped, a
+    /// struct or contract declared in several files is an error.
+    fn read_dir(&self, dir: &Path) -> eyre::Result<(Structs, Structs)> {
+        let mut paths = vec![];
+        sol_files(dir, &mut paths)?;
+        paths.sort();
+        if paths.is_empty() {
+            eyre::bail!("no .sol files found under {}", dir.display());
         }
 
-        let line = line.trim();
-        if line.starts_with("//") {
-            continue;
+        let mut files = vec![];
+        // the file every struct and contract is declared in, by kind and name
+        let mut declared = BTreeMap::<(&str, String), PathBuf>::new();
+        for path in paths {
+            let parsed = read_file(&path).and_then(|file| {
+                if let Some(version) = self.solc_version {
+                    solc::check_features(&file, version)?;
+                }
+                let structs = parse_structs(&file)?;
+                let contracts = if self.contracts {
+                    contract::parse_contracts(&file)?
+                } else {
+                    Default::default()
+                };
+                Ok((file, structs, contracts))
+            });
+            match parsed {
+                Ok((file, structs, contracts)) => {
+                    let names = structs.into_keys().map(|name| ("struct", name));
+                    for (kind, name) in
+                        names.chain(contracts.into_keys().map(|name| ("contract", name)))
+                    {
+                        if let Some(first) = declared.get(&(kind, name.clone())) {
+                            eyre::bail!(
+                                "{kind} {name} is declared in both {} and {}",
+                                first.display(),
+                                path.display()
+                            );
+                        }
+                        declared.insert((kind, name), path.clone());
+                    }
+                    files.push((path, file));
+                }
+                Err(e) => eprintln!("skipping {}: {e}", path.display()),
+            }
         }
 
-        if line.contains("struct") {
-            let st_name = line
-                .split_once("struct")
-                .expect("struct not found")
-                .1
-                .trim()
-                .split_once("{")
-                .expect("{  not found")
-                .0
-                .trim();
-            struct_name = st_name;
-        } else if let Some((bf, _af)) = line.split_once(";") {
-            let splits = bf.split_whitespace().collect::<Vec<_>>();
-            if splits.len() > 1 {
-                let field = splits.iter().last().unwrap().to_string();
-                let typ = splits[..splits.len() - 1].join(" ");
-
-                fields.push((field.replace(";", ""), typ.parse()?))
+        // a file can still fail along with the others, like on a typo `--strict` only catches
+        // once every struct name is known: that one is skipped and the rest parsed again
+        loop {
+            let mut src = String::new();
+            // the line of the joined source every file starts after
+            let mut starts = vec![];
+            for (_, file) in &files {
+                starts.push(src.matches('\n').count());
+                src.push_str(file);
+                src.push('\n');
+            }
+
+            let err = match self.parse(&src) {
+                Ok(parsed) => return Ok(parsed),
+                Err(err) => err,
+            };
+            let file_at = |line: usize| starts.partition_point(|&start| start < line) - 1;
+            let (index, err) = match err {
+                LayoutError::ParseError { line, msg } => {
+                    let index = file_at(line);
+                    let line = line - starts[index];
+                    (index, LayoutError::ParseError { line, msg })
+                }
+                LayoutError::Field { ref owner, .. } => {
+                    let path = ["struct", "contract"]
+                        .into_iter()
+                        .find_map(|kind| declared.get(&(kind, owner.clone())));
+                    let Some(path) = path else {
+                        return Err(err.into());
+                    };
+                    let index = files.iter().position(|(p, _)| p == path);
+                    (index.ok_or_eyre("file of a declaration")?, err)
+                }
+                err => return Err(err.into()),
+            };
+            let (path, _) = files.remove(index);
+            eprintln!("skipping {}: {err}", path.display());
+            if files.is_empty() {
+                eyre::bail!("no .sol file under {} parses", dir.display());
             }
-        } else if line.trim() == "}" {
-            // do nothing
-        } else {
-            eyre::bail!("invalid line: {line}");
         }
     }
+}
 
-    Ok(SolStruct {
-        name: struct_name.to_string(),
-        fields,
-        _inner: src.to_string(),
-    })
+fn contract_reports(contracts: &Structs, structs: &Structs) -> eyre::Result<Vec<LayoutReport>> {
+    Ok(contracts
+        .values()
+        .map(|contract| layout_report(contract, structs))
+        .collect::<Result<Vec<_>, _>>()?)
 }
 
-fn main() -> eyre::Result<()> {
-    println!("reading from stdin..");
+/// Writing a zero slot to a non zero value, cold.
+const SSTORE_GAS: u64 = 20_000;
+/// Reading a cold slot.
+const SLOAD_GAS: u64 = 2_100;
+
+fn read_file(path: &Path) -> eyre::Result<String> {
+    fs::read_to_string(path).map_err(|e| eyre::eyre!("error reading {}: {e}", path.display()))
+}
+
+/// Every `.sol` file under `dir`, recursively, in path order.
+fn sol_files(dir: &Path, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| eyre::eyre!("error reading {}: {e}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            sol_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "sol") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_stdin() -> String {
+    eprintln!("reading from stdin..");
     let stdin = io::stdin();
     let reader = BufReader::new(stdin.lock());
     let mut content = String::new();
@@ -331,30 +391,475 @@ fn main() -> eyre::Result<()> {
         }
     }
 
-    println!("\n--- Content read from stdin ---");
-    println!("{}", content); // Use print! instead of println! to avoid extra newline
-    println!("--- End of stdin ---");
+    eprintln!("\n--- Content read from stdin ---");
+    eprintln!("{}", content); // Use print! instead of println! to avoid extra newline
+    eprintln!("--- End of stdin ---");
+
+    content
+}
+
+/// `slot` in decimal, or as `0x..` with `--hex-slots`.
+fn slot_index(args: &LayoutArgs, slot: u64) -> String {
+    if args.hex_slots {
+        format!("{slot:#x}")
+    } else {
+        slot.to_string()
+    }
+}
+
+fn write_by_slot(out: &mut String, args: &LayoutArgs, report: &LayoutReport) -> eyre::Result<()> {
+    writeln!(out)?;
+    for (slot, fields) in report.fields_by_slot() {
+        let fields = fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.typ))
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "slot {}: [{}]",
+            slot_index(args, slot),
+            fields.join(", ")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The diagram, slot listing, table and free ranges of `report`, whichever were asked for.
+fn write_slot_views(
+    out: &mut String,
+    args: &LayoutArgs,
+    report: &LayoutReport,
+) -> eyre::Result<()> {
+    if args.diagram {
+        write!(out, "\n{}", report.diagram(args.color(), args.hex_slots)?)?;
+    }
+    if args.by_slot {
+        write_by_slot(out, args, report)?;
+    }
+    if args.table {
+        write!(out, "\n{}", report.slot_table(args.hex_slots)?)?;
+    }
+    if args.free {
+        writeln!(out)?;
+        for (slot, range) in report.free_ranges()? {
+            writeln!(
+                out,
+                "slot {}: free [{}..{})",
+                slot_index(args, slot),
+                range.start,
+                range.end
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The table of `report` and its summary, cut down to one number by `--bytes` or `--slots`.
+fn write_report(out: &mut String, args: &LayoutArgs, report: &LayoutReport) -> eyre::Result<()> {
+    let text = if args.hex_slots {
+        format!("{report:#}")
+    } else {
+        format!("{report}")
+    };
+    let (table, summary) = text.rsplit_once('\n').unwrap_or(("", &text));
+    let name = &report.struct_name;
+    writeln!(out, "{table}")?;
+    if args.bytes {
+        writeln!(out, "{name}: {}", report.total_bits.div_ceil(8))?;
+    } else if args.slots {
+        writeln!(out, "{name}: {}", report.total_slots)?;
+    } else {
+        writeln!(out, "{summary}")?;
+        if report.total_slots > 0 {
+            writeln!(
+                out,
+                "{name}: last slot has {} free bytes",
+                report.free_bytes()
+            )?;
+        }
+    }
+
+    Ok(())
+}
 
-    let chunked = chunk_structs(&content)?;
-    // for (i, st) in chunked.iter().enumerate() {
-    //     println!("{i}: {st}");
-    // }
+/// The default output: the table of every struct, followed by whatever extras were asked for,
+/// then the tables of the contracts.
+fn write_text(
+    out: &mut String,
+    args: &LayoutArgs,
+    reports: &[LayoutReport],
+    contract_reports: &[LayoutReport],
+    structs: &BTreeMap<String, SolStruct>,
+) -> eyre::Result<()> {
+    for report in reports.iter().rev() {
+        let name = &report.struct_name;
+        let slots = report.total_slots;
+        write_report(out, args, report)?;
+        write_slot_views(out, args, report)?;
+
+        if args.gas {
+            // packed fields share a slot, so every slot is only paid for once
+            let sstore = args.sstore_gas.unwrap_or(SSTORE_GAS);
+            let sload = args.sload_gas.unwrap_or(SLOAD_GAS);
+            // huge arrays take up more slots than the gas to write them fits in a u64
+            writeln!(
+                out,
+                "{name}: ~{} gas to write ({slots} x {sstore}), ~{} gas to read ({slots} x {sload})",
+                u128::from(slots) * u128::from(sstore),
+                u128::from(slots) * u128::from(sload)
+            )?;
+        }
 
-    let structs = chunked
+        if args.writes {
+            writeln!(
+                out,
+                "{name}: {} SSTORE(s) to write every field once",
+                sstore_count(&structs[name], structs)?
+            )?;
+        }
+
+        if args.optimize {
+            let optimized = optimize_struct(&structs[name], structs)?;
+            let optimized_slots = slot_count(&optimized, structs)?;
+
+            writeln!(out, "\n{name} (optimized):\n-------")?;
+            for (name, typ, _) in &optimized.fields {
+                writeln!(out, "{name}: {typ}")?;
+            }
+            writeln!(
+                out,
+                "{name}: saves {} slot(s) ({slots} -> {optimized_slots})",
+                slots.saturating_sub(optimized_slots)
+            )?;
+        }
+    }
+
+    if args.aggregate {
+        let top_level = top_level_structs(structs);
+        let total = top_level
+            .iter()
+            .map(|st| slot_count(st, structs))
+            .sum::<Result<u64, _>>()?;
+        let names = top_level
+            .iter()
+            .map(|st| st.name.as_str())
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "total: {total} slot(s) for the top level structs {}",
+            names.join(", ")
+        )?;
+    }
+
+    for report in contract_reports {
+        write_report(out, args, report)?;
+        write_slot_views(out, args, report)?;
+    }
+
+    Ok(())
+}
+
+fn layout(args: &LayoutArgs) -> eyre::Result<()> {
+    if args.interactive {
+        return interactive();
+    }
+    if let Some(typ) = &args.typ {
+        let mut structs = match &args.input.inline {
+            Some(src) => parse_structs(src)?,
+            None => Structs::new(),
+        };
+        return write_out(args, &format!("{}\n", evaluate(typ, &mut structs)?));
+    }
+
+    check_extras(args)?;
+    let (structs, contracts) = args.input.read()?;
+    let reports = layout_reports(&structs)?;
+    let contract_reports = contract_reports(&contracts, &structs)?;
+
+    let all_reports = reports
+        .iter()
+        .chain(&contract_reports)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    let mut suboptimal = 0;
+    if let Some(path) = &args.starts_at {
+        writeln!(out, "{}", slot::find_field(path, &all_reports)?.slot)?;
+    } else if let Some(expr) = &args.slot_of {
+        let slot = slot::slot_of(expr, &all_reports)?;
+        writeln!(
+            out,
+            "0x{}",
+            slot.iter().map(|b| format!("{b:02x}")).collect::<String>()
+        )?;
+    } else if args.continuous {
+        let top_level = top_level_structs(&structs)
+            .into_iter()
+            .map(|st| layout_report(st, &structs))
+            .collect::<Result<Vec<_>, _>>()?;
+        let report = concat_reports("continuous", &top_level);
+        write_report(&mut out, args, &report)?;
+        write_slot_views(&mut out, args, &report)?;
+    } else if args.check_optimal {
+        for report in &reports {
+            let name = &report.struct_name;
+            let optimal = slot_count(&optimize_struct(&structs[name], &structs)?, &structs)?;
+            if report.total_slots > optimal {
+                suboptimal += 1;
+                writeln!(
+                    out,
+                    "{name}: SUBOPTIMAL ({} slots, could be {optimal})",
+                    report.total_slots
+                )?;
+            } else {
+                writeln!(out, "{name}: OPTIMAL")?;
+            }
+        }
+    } else {
+        let layouts = reports.iter().rev().chain(&contract_reports);
+        match args.format() {
+            Format::Text => write_text(&mut out, args, &reports, &contract_reports, &structs)?,
+            Format::Json => {
+                let layouts = json::layouts_json(layouts);
+                writeln!(out, "{}", serde_json::to_string_pretty(&layouts)?)?;
+            }
+            Format::Table => {
+                for report in layouts {
+                    writeln!(out, "{}", report.slot_table(args.hex_slots)?)?;
+                }
+            }
+            Format::Csv => {
+                writeln!(out, "{}", csv::CSV_HEADER)?;
+                for report in layouts {
+                    write!(out, "{}", report.csv())?;
+                }
+            }
+            Format::Md => {
+                for report in layouts {
+                    writeln!(out, "{}", report.markdown())?;
+                }
+            }
+            Format::Dot => write!(out, "{}", dot::dot(&layouts.cloned().collect::<Vec<_>>()))?,
+            Format::Diagram => {
+                for report in layouts {
+                    writeln!(out, "{}", report.diagram(args.color(), args.hex_slots)?)?;
+                }
+            }
+        }
+    }
+
+    let mut forge_mismatches = 0;
+    if let Some(path) = &args.compare_forge {
+        if args.format() != Format::Text {
+            eyre::bail!("--compare-forge only goes with the text output");
+        }
+        let mismatches = forge::compare_forge(&read_file(path)?, &structs, &contracts)?;
+
+        writeln!(out, "\n--- forge layout ---")?;
+        for mismatch in &mismatches {
+            writeln!(out, "{mismatch}")?;
+        }
+        if mismatches.is_empty() {
+            writeln!(out, "matches the forge layout")?;
+        }
+        forge_mismatches = mismatches.len();
+    }
+
+    write_out(args, &out)?;
+
+    if forge_mismatches > 0 {
+        eyre::bail!("{forge_mismatches} mismatch(es) against the forge layout");
+    }
+    if suboptimal > 0 {
+        eyre::bail!("{suboptimal} struct(s) could take up fewer slots");
+    }
+
+    if let Some(max_slots) = args.max_slots {
+        let over_budget = reports
+            .iter()
+            .filter(|report| report.total_slots > max_slots)
+            .collect::<Vec<_>>();
+        for report in &over_budget {
+            eprintln!(
+                "warning: {} takes up {} slots, more than the {max_slots} allowed",
+                report.struct_name, report.total_slots
+            );
+        }
+        if !over_budget.is_empty() {
+            eyre::bail!("{} struct(s) over the slot budget", over_budget.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints `out`, or writes it to the file of `--out`.
+fn write_out(args: &LayoutArgs, out: &str) -> eyre::Result<()> {
+    match &args.out {
+        Some(path) => fs::write(path, out)
+            .map_err(|e| eyre::eyre!("error writing {}: {e}", path.display()))?,
+        None => print!("{out}"),
+    }
+
+    Ok(())
+}
+
+/// Fails on the extras of the text output asked for along with an output that doesn't print
+/// them, rather than leaving them out. `--continuous` takes the views of the slots, but not the
+/// extras that go struct by struct.
+fn check_extras(args: &LayoutArgs) -> eyre::Result<()> {
+    let slot_views = [
+        ("--diagram", args.diagram),
+        ("--by-slot", args.by_slot),
+        ("--table", args.table),
+        ("--free", args.free),
+    ];
+    let per_struct = [
+        ("--gas", args.gas),
+        ("--writes", args.writes),
+        ("--optimize", args.optimize),
+        ("--aggregate", args.aggregate),
+    ];
+
+    let format = args.format();
+    let (output, extras) = if args.starts_at.is_some() {
+        ("--starts-at".to_string(), [slot_views, per_struct].concat())
+    } else if args.slot_of.is_some() {
+        ("--slot-of".to_string(), [slot_views, per_struct].concat())
+    } else if args.check_optimal {
+        (
+            "--check-optimal".to_string(),
+            [slot_views, per_struct].concat(),
+        )
+    } else if args.continuous {
+        ("--continuous".to_string(), per_struct.to_vec())
+    } else if format != Format::Text {
+        let name = format
+            .to_possible_value()
+            .ok_or_eyre("every format has a name")?;
+        (
+            format!("--format {}", name.get_name()),
+            [slot_views, per_struct].concat(),
+        )
+    } else {
+        return Ok(());
+    };
+
+    let ignored = extras
         .into_iter()
-        .map(|st| parse_struct(&st).map(|st| (st.name.clone(), st)))
-        .collect::<eyre::Result<BTreeMap<String, SolStruct>>>()?;
+        .filter_map(|(flag, set)| set.then_some(flag))
+        .collect::<Vec<_>>();
+    if !ignored.is_empty() {
+        eyre::bail!(
+            "{} only go(es) with the text output, not with {output}",
+            ignored.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Sizes a single type, or lays out and defines the structs in `input`.
+fn evaluate(input: &str, structs: &mut Structs) -> eyre::Result<String> {
+    if !input.trim_start().starts_with("struct") {
+        let typ = input.parse::<SolType>()?;
+        let bits = typ.size(structs)?;
+        return Ok(format!(
+            "{typ}: {bits} bits, {} slot(s)",
+            snap_to_upper_256(bits) / 256
+        ));
+    }
+
+    let mut out = String::new();
+    for (name, st) in parse_structs(input)? {
+        // a redefinition replaces the old struct
+        structs.insert(name, st.clone());
+        writeln!(out, "{}", layout_report(&st, structs)?)?;
+    }
+    Ok(out.trim_end().to_string())
+}
+
+fn interactive() -> eyre::Result<()> {
+    let prompt = io::stdin().is_terminal();
+    let mut structs = Structs::new();
+    // a struct definition spread over several lines, until its braces balance
+    let mut pending = String::new();
 
-    for (name, st) in structs.iter().rev() {
-        println!("{name}:\n-------");
-        for (name, typ) in &st.fields {
-            println!("{name}: {:?}", typ);
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if prompt {
+            eprint!("{}", if pending.is_empty() { "> " } else { ". " });
+        }
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        if pending.is_empty() && matches!(line.trim(), "" | ":quit") {
+            break;
+        }
+
+        pending.push_str(&line);
+        pending.push('\n');
+        if pending.matches('{').count() > pending.matches('}').count() {
+            continue;
         }
 
-        let size = SolType::Custom(st.clone()).size(&structs)?;
-        let bytes = snap_to_upper_256(size) / 256;
-        println!("{name}: {bytes} [{size}]");
+        match evaluate(&std::mem::take(&mut pending), &mut structs) {
+            Ok(out) => println!("{out}"),
+            Err(e) => println!("error: {e}"),
+        }
     }
 
     Ok(())
 }
+
+fn size(args: &InputArgs) -> eyre::Result<()> {
+    let (structs, contracts) = args.read()?;
+    for report in layout_reports(&structs)?
+        .iter()
+        .chain(&contract_reports(&contracts, &structs)?)
+    {
+        println!("{}: {}", report.struct_name, report.total_slots);
+    }
+
+    Ok(())
+}
+
+fn diff(old: &Path, new: &Path) -> eyre::Result<()> {
+    let layouts = |path: &Path| -> eyre::Result<Vec<LayoutReport>> {
+        let src = read_file(path)?;
+        let structs = parse_structs(&src)?;
+        let mut reports = layout_reports(&structs)?;
+        reports.extend(contract_reports(
+            &contract::parse_contracts(&src)?,
+            &structs,
+        )?);
+        Ok(reports)
+    };
+
+    let changes = diff::diff_layouts(&layouts(old)?, &layouts(new)?);
+    for change in &changes {
+        println!("{change}");
+    }
+    if !changes.is_empty() {
+        eyre::bail!(
+            "{} incompatible change(s) to the storage layout",
+            changes.len()
+        );
+    }
+    println!("storage layout is compatible");
+
+    Ok(())
+}
+
+fn main() -> eyre::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Command::Layout(cli.layout)) {
+        Command::Layout(args) => layout(&args),
+        Command::Size(args) => size(&args),
+        Command::Diff { old, new } => diff(&old, &new),
+    }
+}
@@ -0,0 +1,49 @@
+//! Markdown tables of the computed layouts, for generated docs.
+
+use std::fmt::Write as _;
+
+use crate::LayoutReport;
+
+/// Keeps a cell on a single row of the table.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+impl LayoutReport {
+    /// Renders the layout as a `###` heading followed by a table of the fields and a line with
+    /// the totals. The table only gets a description column when some field has a NatSpec doc.
+    pub fn markdown(&self) -> String {
+        let mut out = String::new();
+        let documented = self.fields.iter().any(|field| field.doc.is_some());
+
+        let _ = writeln!(out, "### {}\n", self.struct_name);
+        if documented {
+            let _ = writeln!(out, "| slot | offset | type | name | description |");
+            let _ = writeln!(out, "| ---: | ---: | --- | --- | --- |");
+        } else {
+            let _ = writeln!(out, "| slot | offset | type | name |");
+            let _ = writeln!(out, "| ---: | ---: | --- | --- |");
+        }
+        for field in &self.fields {
+            let _ = write!(
+                out,
+                "| {} | {} | `{}` | {} |",
+                field.slot,
+                field.offset,
+                field.typ,
+                cell(&field.name)
+            );
+            if documented {
+                let _ = write!(out, " {} |", cell(field.doc.as_deref().unwrap_or_default()));
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(
+            out,
+            "\n{} slot(s), {} bits used",
+            self.total_slots, self.total_bits
+        );
+
+        out
+    }
+}
@@ -0,0 +1,241 @@
+//! Works out the storage slot a mapping entry ends up at, `keccak256(abi.encode(key, slot))`.
+
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{FieldLocation, LayoutError, LayoutReport, SolType};
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    let mut out = [0; 32];
+    hasher.finalize(&mut out);
+    out
+}
+
+/// Parses a decimal or `0x` prefixed hex number into a big endian 256 bit word.
+fn parse_word(s: &str) -> Result<[u8; 32], LayoutError> {
+    let mut word = [0u8; 32];
+
+    let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => (hex, 16),
+        None => (s, 10),
+    };
+    let invalid = || LayoutError::InvalidKey(format!("{s} isn't a number"));
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    for c in digits.chars() {
+        let digit = c.to_digit(radix).ok_or_else(invalid)?;
+        // word = word * radix + digit
+        let mut carry = digit;
+        for byte in word.iter_mut().rev() {
+            let value = *byte as u32 * radix + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return Err(LayoutError::InvalidKey(format!(
+                "number doesn't fit in 256 bits: {s}"
+            )));
+        }
+    }
+
+    Ok(word)
+}
+
+/// Whether `word` fits in its low `bits` bits.
+fn fits(word: &[u8; 32], bits: u32) -> bool {
+    let bits = bits as usize;
+    let full = (256 - bits) / 8;
+    word[..full].iter().all(|&b| b == 0)
+        && (bits.is_multiple_of(8) || word[full] >> (bits % 8) == 0)
+}
+
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, LayoutError> {
+    let hex = s
+        .strip_prefix("0x")
+        .ok_or_else(|| LayoutError::InvalidKey(format!("expected 0x prefixed hex: {s}")))?;
+    if !hex.len().is_multiple_of(2) {
+        return Err(LayoutError::InvalidKey(format!(
+            "odd number of hex digits: {s}"
+        )));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|e| LayoutError::InvalidKey(format!("{s} isn't hex: {e}")))
+        })
+        .collect()
+}
+
+/// ABI encodes `key` the way solidity hashes it as a mapping key of type `typ`: value types
+/// padded to a full word, `bytes` and `string` keys as their raw bytes.
+fn encode_key(typ: &SolType, key: &str) -> Result<Vec<u8>, LayoutError> {
+    let key = key.trim();
+    let too_large = || LayoutError::InvalidKey(format!("{key} doesn't fit in {typ}"));
+    Ok(match typ {
+        SolType::Uint(bits) => {
+            let word = parse_word(key)?;
+            if !fits(&word, (*bits).into()) {
+                return Err(too_large());
+            }
+            word.to_vec()
+        }
+        SolType::Int(bits) => {
+            let (negative, magnitude) = match key.strip_prefix('-') {
+                Some(magnitude) => (true, magnitude),
+                None => (false, key),
+            };
+            let mut word = parse_word(magnitude)?;
+            // -2^(n-1) is the only value whose magnitude takes up all n bits
+            let min = {
+                let mut min = [0u8; 32];
+                let bit = *bits as usize - 1;
+                min[31 - bit / 8] = 1 << (bit % 8);
+                min
+            };
+            if !(fits(&word, u32::from(*bits) - 1) || negative && word == min) {
+                return Err(too_large());
+            }
+
+            if negative {
+                // two's complement, the sign is extended to the whole word
+                for byte in &mut word {
+                    *byte = !*byte;
+                }
+                for byte in word.iter_mut().rev() {
+                    let (value, overflow) = byte.overflowing_add(1);
+                    *byte = value;
+                    if !overflow {
+                        break;
+                    }
+                }
+            }
+            word.to_vec()
+        }
+        SolType::Address => {
+            let word = parse_word(key)?;
+            if !key.starts_with("0x") || !fits(&word, 160) {
+                return Err(LayoutError::InvalidKey(format!("{key} isn't an address")));
+            }
+            word.to_vec()
+        }
+        SolType::Bool => match key {
+            "true" => parse_word("1")?.to_vec(),
+            "false" => parse_word("0")?.to_vec(),
+            _ => return Err(LayoutError::InvalidKey(format!("{key} isn't a bool"))),
+        },
+        // left aligned, unlike numbers
+        SolType::Bytes(len) => {
+            let bytes = parse_hex_bytes(key)?;
+            if bytes.len() > *len as usize {
+                return Err(too_large());
+            }
+            let mut word = vec![0; 32];
+            word[..bytes.len()].copy_from_slice(&bytes);
+            word
+        }
+        SolType::BytesArbitrary | SolType::String => {
+            match key.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                Some(string) => string.as_bytes().to_vec(),
+                None => parse_hex_bytes(key)?,
+            }
+        }
+        _ => {
+            return Err(LayoutError::InvalidKey(format!(
+                "unsupported mapping key type: {typ}"
+            )));
+        }
+    })
+}
+
+/// The slot of the entry at `key` of the mapping stored at `slot`, with `key_type` keys.
+pub fn mapping_slot(
+    slot: [u8; 32],
+    key_type: &SolType,
+    key: &str,
+) -> Result<[u8; 32], LayoutError> {
+    let mut data = encode_key(key_type, key)?;
+    data.extend_from_slice(&slot);
+    Ok(keccak256(&data))
+}
+
+/// The top level field at `path`, a field name optionally prefixed with its struct or contract
+/// name like `Vault.balances`. The prefix is needed when several of `reports` have a field by
+/// that name.
+pub fn find_field<'a>(
+    path: &str,
+    reports: &'a [LayoutReport],
+) -> Result<&'a FieldLocation, LayoutError> {
+    // the owner can be a qualified struct name itself, `Vault.Order.price`
+    let (owner, field_name) = match path.trim().rsplit_once('.') {
+        Some((owner, field)) => (Some(owner), field),
+        None => (None, path.trim()),
+    };
+
+    let mut candidates = reports
+        .iter()
+        .filter(|report| owner.is_none_or(|owner| report.struct_name == owner))
+        .filter_map(|report| {
+            report
+                .fields
+                .iter()
+                .find(|field| field.name == field_name)
+                .map(|field| (report, field))
+        });
+    let (report, field) = candidates
+        .next()
+        .ok_or_else(|| LayoutError::UnknownField(path.trim().to_string()))?;
+    if let Some((other, _)) = candidates.next() {
+        return Err(LayoutError::AmbiguousField {
+            field: field_name.to_string(),
+            owners: [report.struct_name.clone(), other.struct_name.clone()],
+        });
+    }
+
+    Ok(field)
+}
+
+/// Resolves `expr`, a mapping field followed by one `[key]` per level of nesting like
+/// `balances[0xabc..]` or `allowances[0xabc..][0xdef..]`, to the slot the entry is stored at.
+///
+/// The field can be prefixed with its struct or contract name, `Vault.balances[..]`, and has to
+/// be when several of `reports` have a field by that name. Slots of struct fields are relative to
+/// the first slot of the struct.
+pub fn slot_of(expr: &str, reports: &[LayoutReport]) -> Result<[u8; 32], LayoutError> {
+    let (path, keys) = expr.split_at(expr.find('[').unwrap_or(expr.len()));
+    let field = find_field(path, reports)?;
+
+    let mut slot = [0u8; 32];
+    slot[24..].copy_from_slice(&field.slot.to_be_bytes());
+    let mut typ = &field.typ;
+
+    let mut rest = keys.trim();
+    if rest.is_empty() {
+        return Err(LayoutError::InvalidSlotExpr(format!(
+            "{expr} has no [key] to look up"
+        )));
+    }
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .ok_or_else(|| {
+                LayoutError::InvalidSlotExpr(format!("expected [key] in {expr} at {rest}"))
+            })?;
+        let SolType::Mapping(key_type, value_type) = typ else {
+            return Err(LayoutError::InvalidSlotExpr(format!(
+                "{typ} isn't a mapping, can't look up [{key}] in it"
+            )));
+        };
+
+        slot = mapping_slot(slot, key_type, key)?;
+        typ = value_type;
+        rest = after.trim_start();
+    }
+
+    Ok(slot)
+}
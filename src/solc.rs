@@ -0,0 +1,81 @@
+//! Checks the source against the solc release it's meant for, as the language features that
+//! reach storage came in over several releases.
+
+use std::{fmt, str::FromStr, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::{LayoutError, contract::strip_comments};
+
+/// A solc release, like `0.8.28`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SolcVersion(pub u64, pub u64, pub u64);
+
+impl fmt::Display for SolcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+impl FromStr for SolcVersion {
+    type Err = LayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || LayoutError::InvalidSolcVersion(s.to_string());
+        let parts = s
+            .trim()
+            .split('.')
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        match parts[..] {
+            [major, minor, patch] => Ok(Self(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The features the layout depends on, by the release that introduced them and a pattern
+/// telling them apart in the source.
+static FEATURES: LazyLock<[(&str, SolcVersion, Regex); 3]> = LazyLock::new(|| {
+    let regex = |pattern| Regex::new(pattern).expect("valid feature regex");
+    [
+        (
+            "custom errors",
+            SolcVersion(0, 8, 4),
+            regex(r"\berror\s+\w+\s*\("),
+        ),
+        (
+            "user defined value types",
+            SolcVersion(0, 8, 8),
+            regex(r"\btype\s+\w+\s+is\b"),
+        ),
+        (
+            "transient storage",
+            SolcVersion(0, 8, 28),
+            regex(r"\w\s+transient\s+\w"),
+        ),
+    ]
+});
+
+/// Fails on the first feature used in `src` that `version` doesn't have yet, pointing at the
+/// line using it. Comments don't count.
+pub fn check_features(src: &str, version: SolcVersion) -> Result<(), LayoutError> {
+    let src = strip_comments(src);
+    for (feature, since, regex) in FEATURES.iter() {
+        if version >= *since {
+            continue;
+        }
+
+        if let Some(found) = regex.find(&src) {
+            return Err(LayoutError::UnavailableFeature {
+                feature,
+                line: src[..found.start()].matches('\n').count() + 1,
+                since: *since,
+                version,
+            });
+        }
+    }
+
+    Ok(())
+}
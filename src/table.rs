@@ -0,0 +1,66 @@
+//! Flat table of the fields and padding in every slot of a struct, for decoding raw storage.
+
+use std::fmt::Write as _;
+
+use crate::{LayoutError, LayoutReport, truncate_type};
+
+impl LayoutReport {
+    /// One row per run of bytes in every slot: the slot, its byte range, and the type and name
+    /// of the field stored there, or `-` and `(padding)` for unused bytes.
+    ///
+    /// Rows go slot by slot, lowest byte first, with ranges inclusive and bytes numbered the way
+    /// [`FieldLocation::offset`](crate::FieldLocation::offset) counts them. Fields that aren't
+    /// value types get a row for every slot they take up. With `hex` slots are numbered `0x..`.
+    pub fn slot_table(&self, hex: bool) -> Result<String, LayoutError> {
+        let mut rows = vec![];
+        for (slot, bytes) in self.byte_occupancy()?.iter().enumerate() {
+            let mut start = 0;
+            while start < bytes.len() {
+                let len = bytes[start..]
+                    .iter()
+                    .take_while(|&&byte| byte == bytes[start])
+                    .count();
+                let (typ, name) = match bytes[start] {
+                    Some(field) => (
+                        truncate_type(&self.fields[field].typ.to_string()),
+                        self.fields[field].name.clone(),
+                    ),
+                    None => ("-".to_string(), "(padding)".to_string()),
+                };
+                rows.push([
+                    if hex {
+                        format!("{slot:#x}")
+                    } else {
+                        slot.to_string()
+                    },
+                    format!("{start}-{}", start + len - 1),
+                    typ,
+                    name,
+                ]);
+                start += len;
+            }
+        }
+
+        let header = ["slot", "bytes", "type", "name"];
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}:\n-------", self.struct_name);
+        for row in std::iter::once(header.map(String::from)).chain(rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+
+        Ok(out)
+    }
+}
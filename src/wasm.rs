@@ -0,0 +1,16 @@
+//! Browser bindings, behind the `wasm` feature.
+
+use serde_json::json;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{compute_layout, json::layouts_json};
+
+/// Lays out every struct in the Solidity source `src`, returning the same json as `--json`, or an `{"error": ".."}` object when `src` doesn't parse.
+#[wasm_bindgen]
+pub fn compute_layout_json(src: &str) -> String {
+    match compute_layout(src) {
+        Ok(reports) => layouts_json(&reports),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+    .to_string()
+}
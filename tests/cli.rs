@@ -0,0 +1,173 @@
+use std::{fs, path::PathBuf, process::Command};
+
+/// Runs the binary with `args`, returning its exit status, stdout and stderr.
+fn run(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_struct-storage-layout"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// A fresh directory holding `files`, by name and source.
+fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ssl-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, src) in files {
+        fs::write(dir.join(file), src).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn dir_structs_refer_across_files() {
+    let dir = dir(
+        "across",
+        &[
+            ("a.sol", "struct A { uint128 x; B b; }"),
+            ("b.sol", "struct B { uint256 y; }"),
+        ],
+    );
+    let (ok, stdout, _) = run(&[dir.to_str().unwrap(), "--strict"]);
+    assert!(ok);
+    assert!(stdout.contains("A: 2 [512]"), "{stdout}");
+}
+
+#[test]
+fn dir_strict_errors_point_at_the_file_and_its_line() {
+    let dir = dir(
+        "strict",
+        &[
+            ("a.sol", "struct A {\n  uint256 x;\n}\n\n\n\n"),
+            ("b.sol", "struct B {\n  unit256 y;\n}\n"),
+        ],
+    );
+    let (ok, stdout, stderr) = run(&[dir.to_str().unwrap(), "--strict"]);
+    assert!(ok);
+    let b = dir.join("b.sol");
+    assert!(
+        stderr.contains(&format!(
+            "skipping {}: line 2: unknown type unit256",
+            b.display()
+        )),
+        "{stderr}"
+    );
+    assert!(stdout.contains("A: 1 [256]"), "{stdout}");
+}
+
+#[test]
+fn dir_ambiguous_struct_skips_its_file() {
+    let dir = dir(
+        "ambiguous",
+        &[
+            (
+                "a.sol",
+                "contract V { struct O { uint a; } }\ncontract W { struct O { uint b; } }",
+            ),
+            ("b.sol", "struct D { O o; }"),
+        ],
+    );
+    let (ok, stdout, stderr) = run(&[dir.to_str().unwrap()]);
+    assert!(ok);
+    assert!(
+        stderr.contains("b.sol: error parsing D.o: ambiguous struct O"),
+        "{stderr}"
+    );
+    assert!(
+        stdout.contains("V.O:") && !stdout.contains("D:"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn dir_contract_declared_twice() {
+    let dir = dir(
+        "contracts",
+        &[
+            ("a.sol", "contract Foo { uint a; }"),
+            ("b.sol", "contract Foo { uint b; }"),
+        ],
+    );
+    let (ok, _, stderr) = run(&[dir.to_str().unwrap(), "--contracts"]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("contract Foo is declared in both"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn dir_struct_declared_twice() {
+    let dir = dir(
+        "structs",
+        &[
+            ("a.sol", "struct S { uint a; }"),
+            ("b.sol", "struct S { uint b; }"),
+        ],
+    );
+    let (ok, _, stderr) = run(&[dir.to_str().unwrap()]);
+    assert!(!ok);
+    assert!(stderr.contains("struct S is declared in both"), "{stderr}");
+}
+
+#[test]
+fn extras_are_rejected_with_other_formats() {
+    let src = "struct S { uint128 a; uint256 b; }";
+    let (ok, _, stderr) = run(&["--inline", src, "--format", "csv", "--diagram", "--gas"]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("--diagram, --gas only go(es) with the text output, not with --format csv"),
+        "{stderr}"
+    );
+
+    let (ok, _, stderr) = run(&["--inline", src, "--continuous", "--writes"]);
+    assert!(!ok);
+    assert!(stderr.contains("not with --continuous"), "{stderr}");
+}
+
+#[test]
+fn continuous_draws_the_diagram() {
+    let src = "struct S { uint128 a; uint256 b; }";
+    let (ok, stdout, _) = run(&["--inline", src, "--continuous", "--diagram"]);
+    assert!(ok);
+    assert!(stdout.contains("slot 1 |bbbb"), "{stdout}");
+}
+
+#[test]
+fn hex_slots_in_table_and_diagram() {
+    let src = "struct S { uint128 a; uint256[16] b; }";
+    let (ok, stdout, _) = run(&["--inline", src, "--hex-slots", "--table", "--diagram"]);
+    assert!(ok);
+    assert!(stdout.contains("slot 0x10 |bbbb"), "{stdout}");
+    assert!(stdout.contains("0x10  0-31   uint256[16]  b"), "{stdout}");
+    assert!(!stdout.contains("\n16 "), "{stdout}");
+}
+
+#[test]
+fn type_writes_to_out() {
+    let dir = dir("type-out", &[]);
+    let out = dir.join("size.txt");
+    let (ok, stdout, _) = run(&["--type", "uint128[3]", "--out", out.to_str().unwrap()]);
+    assert!(ok);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        fs::read_to_string(out).unwrap(),
+        "uint128[3]: 512 bits, 2 slot(s)\n"
+    );
+}
+
+#[test]
+fn aggregate_counts_top_level_structs_once() {
+    let src = "struct Inner { uint256 a; uint256 b; } struct Outer { Inner inner; uint8 c; }";
+    let (ok, stdout, _) = run(&["--inline", src, "--aggregate"]);
+    assert!(ok);
+    assert_eq!(
+        stdout.lines().last(),
+        Some("total: 3 slot(s) for the top level structs Outer")
+    );
+}
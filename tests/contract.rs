@@ -0,0 +1,53 @@
+use struct_storage_layout::contract::parse_contracts;
+
+/// The state variables of `contract`, by name.
+fn variables(src: &str, contract: &str) -> Vec<String> {
+    parse_contracts(src).unwrap()[contract]
+        .fields
+        .iter()
+        .map(|(name, _, _)| name.clone())
+        .collect()
+}
+
+#[test]
+fn functions_events_and_modifiers_arent_storage() {
+    let src = "
+        contract Vault {
+            address owner;
+            event Deposit(address indexed from, uint256 amount);
+            modifier onlyOwner() {
+                require(msg.sender == owner);
+                _;
+            }
+            constructor() {
+                owner = msg.sender;
+            }
+            uint128 total;
+            function deposit(uint256 amount) external onlyOwner {
+                if (amount > 0) { total += uint128(amount); }
+            }
+            function get() external view returns (uint256) { return total; }
+            bool paused;
+        }
+    ";
+    assert_eq!(variables(src, "Vault"), ["owner", "total", "paused"]);
+}
+
+#[test]
+fn assembly_and_unchecked_blocks_arent_storage() {
+    let src = "
+        contract Vault {
+            uint256 total;
+            function bump() external {
+                assembly {
+                    let slot := total.slot
+                    if iszero(sload(slot)) { sstore(slot, 1) }
+                }
+                unchecked { total += 1; }
+                { uint256 scratch = total; delete scratch; }
+            }
+            address owner;
+        }
+    ";
+    assert_eq!(variables(src, "Vault"), ["total", "owner"]);
+}
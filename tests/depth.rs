@@ -0,0 +1,56 @@
+use struct_storage_layout::{LayoutError, SolType, compute_layout, layout_reports, parse_structs};
+
+fn nested_mappings(depth: usize) -> String {
+    format!(
+        "{}uint{}",
+        "mapping(uint => ".repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+#[test]
+fn deeply_nested_mappings_dont_parse() {
+    for depth in [300, 20_000] {
+        let err = nested_mappings(depth).parse::<SolType>().unwrap_err();
+        let LayoutError::TooDeep(typ) = err else {
+            panic!("{err}");
+        };
+        assert!(typ.len() < 100, "{typ}");
+        assert!(typ.starts_with("mapping(uint => mapping("), "{typ}");
+    }
+}
+
+#[test]
+fn deeply_nested_arrays_dont_parse() {
+    let typ = format!("uint{}", "[1]".repeat(2_000));
+    assert!(matches!(
+        typ.parse::<SolType>(),
+        Err(LayoutError::TooDeep(_))
+    ));
+}
+
+#[test]
+fn deeply_nested_struct_fields() {
+    let src = format!("struct S {{ {} m; }}", nested_mappings(5_000));
+    assert!(matches!(compute_layout(&src), Err(LayoutError::TooDeep(_))));
+}
+
+#[test]
+fn long_type_names_are_cut_in_the_table() {
+    let src = format!("struct S {{ {} m; }}", nested_mappings(200));
+    let structs = parse_structs(&src).unwrap();
+    let report = &layout_reports(&structs).unwrap()[0];
+    let text = report.to_string();
+    assert!(text.lines().all(|line| line.len() < 200), "{text}");
+    assert!(text.contains("mapping(uint256 => mapp..."), "{text}");
+    assert!(report.slot_table(false).is_ok());
+}
+
+#[test]
+fn deeply_nested_structs() {
+    let mut src = String::from("struct S0 { uint256 x; }");
+    for i in 1..=300 {
+        src.push_str(&format!("struct S{i} {{ S{} inner; }}", i - 1));
+    }
+    assert!(matches!(compute_layout(&src), Err(LayoutError::TooDeep(_))));
+}
@@ -0,0 +1,29 @@
+use struct_storage_layout::{LayoutError, SolType, fields::parse_fields_json};
+
+#[test]
+fn fields_in_order() {
+    let st = parse_fields_json(
+        "Pair",
+        r#"[{"name": "a", "type": "uint128"}, {"name": "b", "type": "mapping(address => bool)"}]"#,
+    )
+    .unwrap();
+    assert_eq!(st.name, "Pair");
+    assert_eq!(st.fields[0].1, SolType::Uint(128));
+    assert_eq!(st.fields[1].0, "b");
+}
+
+#[test]
+fn invalid_fields() {
+    assert!(matches!(
+        parse_fields_json("S", "{"),
+        Err(LayoutError::InvalidJson(_))
+    ));
+    assert!(matches!(
+        parse_fields_json("S", r#"[{"type": "uint8"}]"#),
+        Err(LayoutError::InvalidJson(_))
+    ));
+    assert!(matches!(
+        parse_fields_json("S", r#"[{"name": "a", "type": "uint["}]"#),
+        Err(LayoutError::Field { .. })
+    ));
+}
@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+import "./Ownable.sol";
+
+interface IERC20 {
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+/// @notice Fixed point prices with 18 decimals.
+type Price is uint128;
+
+uint256 constant MAX_LEVELS = 4;
+
+error Unauthorized(address caller);
+
+enum Side {
+    Buy,
+    Sell
+}
+
+interface IOracle {
+    struct Quote {
+        Price price;
+        uint64 updatedAt;
+    }
+
+    event Updated(address indexed asset, Price price);
+
+    function quote(address asset) external view returns (Quote memory);
+}
+
+library OrderLib {
+    struct Order {
+        address maker;
+        Side side;
+        bool filled;
+        Price price;
+        uint128 amount;
+    }
+
+    function isOpen(Order storage order) internal view returns (bool) {
+        return !order.filled && order.amount > 0;
+    }
+}
+
+abstract contract Pausable {
+    bool internal paused;
+
+    modifier whenNotPaused() {
+        if (paused) {
+            revert("paused");
+        }
+        _;
+    }
+}
+
+contract Exchange is Pausable {
+    using OrderLib for OrderLib.Order;
+
+    struct Level {
+        Price price;
+        uint128 depth;
+        OrderLib.Order[] orders;
+    }
+
+    struct Book {
+        IERC20 base;
+        IERC20 quote;
+        Level[MAX_LEVELS] bids;
+        Level[MAX_LEVELS] asks;
+        mapping(address => uint256) balances;
+    }
+
+    event Filled(uint256 indexed id, address taker);
+
+    mapping(bytes32 => Book) internal books;
+    IOracle public oracle;
+    uint64 public nextId;
+
+    constructor(IOracle oracle_) {
+        oracle = oracle_;
+    }
+
+    function fill(bytes32 market, uint256 level, uint256 index) external whenNotPaused {
+        Book storage book = books[market];
+        OrderLib.Order storage order = book.bids[level].orders[index];
+        if (!order.isOpen()) {
+            revert Unauthorized(msg.sender);
+        }
+        unchecked {
+            order.filled = true;
+        }
+        emit Filled(nextId++, msg.sender);
+    }
+
+    receive() external payable {}
+}
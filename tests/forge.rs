@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+
+use struct_storage_layout::{contract::parse_contracts, forge::compare_forge, parse_structs};
+
+const SRC: &str = "
+    struct Pair { uint128 a; uint128 b; }
+    contract Vault { address owner; bool paused; Pair pair; }
+";
+
+#[test]
+fn matching_struct() {
+    let structs = parse_structs(SRC).unwrap();
+    let forge = r#"{"storage": [], "types": {"t_struct(Pair)1_storage": {
+        "label": "struct Pair", "numberOfBytes": "32", "members": [
+            {"label": "a", "offset": 0, "slot": "0"},
+            {"label": "b", "offset": 16, "slot": "0"}
+        ]}}}"#;
+    assert_eq!(
+        compare_forge(forge, &structs, &BTreeMap::new()).unwrap(),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn contract_storage_mismatch() {
+    let structs = parse_structs(SRC).unwrap();
+    let contracts = parse_contracts(SRC).unwrap();
+    let forge = r#"{"types": {}, "storage": [
+        {"contract": "src/Vault.sol:Vault", "label": "owner", "offset": 0, "slot": "0"},
+        {"contract": "src/Vault.sol:Vault", "label": "paused", "offset": 20, "slot": "0"},
+        {"contract": "src/Vault.sol:Vault", "label": "pair", "offset": 0, "slot": "2"}
+    ]}"#;
+    assert_eq!(
+        compare_forge(forge, &structs, &contracts).unwrap(),
+        ["Vault.pair: slot 1 but forge has \"2\""]
+    );
+}
+
+#[test]
+fn storage_needs_the_contracts() {
+    let structs = parse_structs(SRC).unwrap();
+    let forge = r#"{"types": {}, "storage": [
+        {"contract": "src/Vault.sol:Vault", "label": "owner", "offset": 0, "slot": "0"}
+    ]}"#;
+    assert!(compare_forge(forge, &structs, &BTreeMap::new()).is_err());
+}
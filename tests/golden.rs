@@ -0,0 +1,88 @@
+//! The layouts of the `.sol` files in `tests/golden`, slot for slot against the storage layout
+//! solc gives for them in the `.json` next to each, the `storageLayout` of `solc --combined-json
+//! storage-layout`.
+
+use std::{collections::BTreeMap, fs};
+
+use serde_json::Value;
+use struct_storage_layout::{
+    SolStruct, contract::parse_contracts, field_locations, parse_structs, slot_count,
+};
+
+/// Slot, byte offset and label of every member of a solc struct type or storage array.
+fn solc_members(members: &Value) -> Vec<(u64, u64, String)> {
+    members
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| {
+            (
+                member["slot"].as_str().unwrap().parse().unwrap(),
+                member["offset"].as_u64().unwrap(),
+                member["label"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn members(st: &SolStruct, structs: &BTreeMap<String, SolStruct>) -> Vec<(u64, u64, String)> {
+    field_locations(st, structs)
+        .unwrap()
+        .into_iter()
+        .map(|field| (field.slot, field.offset, field.name))
+        .collect()
+}
+
+fn check(name: &str) {
+    let src = fs::read_to_string(format!("tests/golden/{name}.sol")).unwrap();
+    let solc: Value =
+        serde_json::from_str(&fs::read_to_string(format!("tests/golden/{name}.json")).unwrap())
+            .unwrap();
+    let structs = parse_structs(&src).unwrap();
+    let contracts = parse_contracts(&src).unwrap();
+
+    assert_eq!(
+        members(&contracts[name], &structs),
+        solc_members(&solc["storage"]),
+        "{name}"
+    );
+
+    let mut compared = vec![];
+    for typ in solc["types"].as_object().unwrap().values() {
+        let Some(label) = typ["label"].as_str().unwrap().strip_prefix("struct ") else {
+            continue;
+        };
+        // array types label their elements the same way
+        let Some(st) = structs.get(label) else {
+            continue;
+        };
+        assert_eq!(
+            members(st, &structs),
+            solc_members(&typ["members"]),
+            "{label}"
+        );
+        assert_eq!(
+            (slot_count(st, &structs).unwrap() * 32).to_string(),
+            typ["numberOfBytes"].as_str().unwrap(),
+            "{label}"
+        );
+        compared.push(label);
+    }
+    compared.sort();
+    assert_eq!(compared, structs.keys().collect::<Vec<_>>(), "{name}");
+}
+
+#[test]
+fn packing() {
+    check("Packing");
+}
+
+#[test]
+fn mappings_and_arrays() {
+    check("Collections");
+}
+
+#[test]
+fn nested_structs_and_enums() {
+    check("Nested");
+}
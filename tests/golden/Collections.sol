@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+contract Collections {
+    uint8 flag;
+    mapping(address => uint256) balances;
+    mapping(address => mapping(uint256 => bool)) approvals;
+    uint256[] list;
+    uint16[5] small;
+    bytes32[2] hashes;
+    bool[33] bits;
+    address[3] admins;
+    string name;
+    bytes data;
+    uint8 tail;
+}
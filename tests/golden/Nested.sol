@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+enum Status {
+    None,
+    Active,
+    Closed
+}
+
+struct Inner {
+    uint64 a;
+    Status s;
+    bool ok;
+}
+
+struct Outer {
+    uint256 id;
+    Inner inner;
+    Status[4] history;
+    uint8 flags;
+    mapping(uint256 => Inner) byId;
+    Inner[] list;
+    Inner[2] pair;
+}
+
+contract Nested {
+    struct Position {
+        address owner;
+        uint96 size;
+    }
+
+    Status status;
+    Outer outer;
+    uint32 count;
+    Inner[] inners;
+    Position pos;
+}
@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+contract Packing {
+    uint8 a;
+    uint16 b;
+    bool c;
+    address owner;
+    uint128 x;
+    uint128 y;
+    uint256 z;
+    bytes4 sel;
+    int64 i;
+    bool done;
+}
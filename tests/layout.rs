@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+
+use struct_storage_layout::{
+    LayoutError, SolStruct, SolType, StorageLayout, layout_report, optimize_struct, parse_structs,
+    slot_count, top_level_structs,
+};
+
+fn structs(src: &str) -> BTreeMap<String, SolStruct> {
+    parse_structs(src).unwrap()
+}
+
+#[test]
+fn optimize_saves_a_slot() {
+    let structs = structs("struct S { uint128 a; uint256 b; uint128 c; }");
+    assert_eq!(slot_count(&structs["S"], &structs).unwrap(), 3);
+
+    let optimized = optimize_struct(&structs["S"], &structs).unwrap();
+    assert_eq!(slot_count(&optimized, &structs).unwrap(), 2);
+    let mut names = optimized
+        .fields
+        .iter()
+        .map(|(name, _, _)| name.as_str())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["a", "b", "c"]);
+}
+
+#[test]
+fn optimize_keeps_the_order_of_anchored_fields() {
+    let src = "struct S { uint8 a; mapping(uint => uint) m; uint248 b; uint256[] xs; uint8 c; }";
+    let structs = structs(src);
+    assert_eq!(slot_count(&structs["S"], &structs).unwrap(), 5);
+    let optimized = optimize_struct(&structs["S"], &structs).unwrap();
+    let anchored = optimized
+        .fields
+        .iter()
+        .filter(|(_, typ, _)| typ.forces_new_slot())
+        .map(|(name, _, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(anchored, ["m", "xs"]);
+    // a and b share a slot, c gets one of its own
+    assert_eq!(slot_count(&optimized, &structs).unwrap(), 4);
+}
+
+fn slots(src: &str, name: &str) -> u64 {
+    let structs = structs(src);
+    slot_count(&structs[name], &structs).unwrap()
+}
+
+#[test]
+fn arrays_of_mappings() {
+    assert_eq!(slots("struct S { mapping(uint => uint)[] ms; }", "S"), 1);
+    assert_eq!(
+        slots("struct S { mapping(uint => uint)[3] ms; uint8 b; }", "S"),
+        4
+    );
+}
+
+#[test]
+fn offset_of_nested_fields() {
+    let src = "
+        struct Leaf { uint64 x; uint128 y; }
+        struct Mid { uint8 flag; Leaf leaf; }
+        struct Outer { uint256 head; uint8 tag; Mid mid; }
+    ";
+    let structs = structs(src);
+    let layout = StorageLayout::new(&structs["Outer"], &structs).unwrap();
+    // head, tag, then mid on slot 2 with its flag, and leaf on a fresh slot after it
+    assert_eq!(layout.offset_of(&["tag"]), Some((1, 0)));
+    assert_eq!(layout.offset_of(&["mid", "flag"]), Some((2, 0)));
+    assert_eq!(layout.offset_of(&["mid", "leaf", "x"]), Some((3, 0)));
+    assert_eq!(layout.offset_of(&["mid", "leaf", "y"]), Some((3, 8)));
+    assert_eq!(layout.offset_of(&["mid", "nope"]), None);
+}
+
+#[test]
+fn huge_fixed_arrays_dont_overflow() {
+    let structs = structs("struct S { uint256[1000000000000000000] huge; }");
+    assert_eq!(
+        slot_count(&structs["S"], &structs),
+        Err(LayoutError::ArrayTooLarge)
+    );
+
+    // the slot count only fits in a u64 while its bits do too
+    let typ = "uint256[72057594037927935]".parse::<SolType>().unwrap();
+    assert_eq!(typ.size_slots(&structs).unwrap(), 72057594037927935);
+    let typ = "uint256[2][72057594037927935]".parse::<SolType>().unwrap();
+    assert_eq!(typ.size(&structs), Err(LayoutError::ArrayTooLarge));
+}
+
+/// Slot and offset of every field of `name`, by field name.
+fn locations(src: &str, name: &str) -> Vec<(String, u64, u64)> {
+    let structs = structs(src);
+    StorageLayout::new(&structs[name], &structs)
+        .unwrap()
+        .fields
+        .into_iter()
+        .map(|field| (field.name, field.slot, field.offset))
+        .collect()
+}
+
+#[test]
+fn fields_after_a_fixed_array_start_a_new_slot() {
+    assert_eq!(
+        locations("struct S { uint128[3] a; uint8 b; }", "S"),
+        [("a".to_string(), 0, 0), ("b".to_string(), 2, 0)]
+    );
+}
+
+#[test]
+fn arrays_of_fixed_bytes() {
+    assert_eq!(slots("struct S { bytes32[] hashes; }", "S"), 1);
+    assert_eq!(slots("struct S { bytes4[8] selectors; }", "S"), 1);
+    assert_eq!(slots("struct S { bytes4[9] selectors; }", "S"), 2);
+    assert_eq!(
+        locations("struct S { bytes4[8] selectors; bytes4 more; }", "S"),
+        [("selectors".to_string(), 0, 0), ("more".to_string(), 1, 0)]
+    );
+}
+
+#[test]
+fn structs_behind_mappings_and_arrays_arent_top_level() {
+    let src = "
+        struct Leaf { uint256 x; }
+        struct Held { uint256 y; }
+        struct Listed { uint256 z; }
+        struct Node { uint256 v; mapping(uint256 => Node) children; }
+        struct Root { Leaf leaf; mapping(address => Held) held; Listed[] listed; }
+    ";
+    let structs = structs(src);
+    let top_level = top_level_structs(&structs)
+        .into_iter()
+        .map(|st| st.name.as_str())
+        .collect::<Vec<_>>();
+    // a struct referring to itself is still top level
+    assert_eq!(top_level, ["Node", "Root"]);
+}
+
+#[test]
+fn layouts_of_identical_structs_compare_equal() {
+    let compact = structs("struct S { uint128 a; mapping(uint => uint[2])[] m; }");
+    let spread = structs(
+        "
+        // the same struct, written out differently
+        struct S {
+            uint128 a;
+            mapping(uint256 => uint256[2])[] m;
+        }
+    ",
+    );
+    assert_eq!(compact["S"], spread["S"]);
+    assert_eq!(
+        StorageLayout::new(&compact["S"], &compact).unwrap(),
+        StorageLayout::new(&spread["S"], &spread).unwrap()
+    );
+    assert_ne!(compact["S"], structs("struct S { uint128 b; }")["S"]);
+}
+
+#[test]
+fn dynamic_arrays_of_arrays() {
+    assert_eq!(
+        "uint[][]".parse::<SolType>().unwrap(),
+        SolType::Array(Box::new(SolType::Array(Box::new(SolType::Uint(256)))))
+    );
+    assert_eq!(slots("struct S { uint[][] grid; }", "S"), 1);
+
+    assert_eq!(
+        "uint[][3]".parse::<SolType>().unwrap(),
+        SolType::FixedArray(Box::new(SolType::Array(Box::new(SolType::Uint(256)))), 3)
+    );
+    assert_eq!(slots("struct S { uint[][3] rows; }", "S"), 3);
+}
+
+#[test]
+fn struct_valued_mappings() {
+    let src = "
+        struct UserInfo { uint256 amount; uint256 debt; }
+        struct Pool { mapping(address => UserInfo) users; UserInfo[] history; }
+    ";
+    assert_eq!(slots(src, "Pool"), 2);
+
+    let unknown = structs("struct Pool { mapping(address => UserInfo) users; }");
+    assert_eq!(
+        slot_count(&unknown["Pool"], &unknown),
+        Err(LayoutError::UnknownStruct("UserInfo".to_string()))
+    );
+}
+
+#[test]
+fn contract_and_interface_typed_fields_are_addresses() {
+    let src = "
+        interface IERC20 { function transfer(address to, uint256 amount) external returns (bool); }
+        contract Vault {}
+        struct S { IERC20 token; uint64 a; mapping(address => IERC20) byOwner; Vault vault; }
+    ";
+    assert_eq!(
+        locations(src, "S"),
+        [
+            ("token".to_string(), 0, 0),
+            ("a".to_string(), 0, 20),
+            ("byOwner".to_string(), 1, 0),
+            ("vault".to_string(), 2, 0),
+        ]
+    );
+    assert_eq!(structs(src)["S"].fields[0].1, SolType::Address);
+}
+
+#[test]
+fn enum_arrays_pack_as_bytes() {
+    let src =
+        "enum Status { Open, Closed } struct S { Status[40] history; Status[] log; Status s; }";
+    assert_eq!(slots(src, "S"), 4);
+    assert_eq!(
+        slots("enum Status { A } struct S { Status[8] history; }", "S"),
+        1
+    );
+}
+
+#[test]
+fn arrays_of_structs() {
+    let src = "
+        struct Order { uint256 price; uint64 amount; }
+        struct Book { Order[] orders; Order[3] top; uint8 after; }
+    ";
+    let structs = structs(src);
+    let order = || Box::new(SolType::Custom2("Order".to_string()));
+    let types = structs["Book"]
+        .fields
+        .iter()
+        .map(|(_, typ, _)| typ.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types[..2],
+        [SolType::Array(order()), SolType::FixedArray(order(), 3)]
+    );
+    // every Order starts on a fresh slot, the amount doesn't share one with the next price
+    assert_eq!(
+        locations(src, "Book"),
+        [
+            ("orders".to_string(), 0, 0),
+            ("top".to_string(), 1, 0),
+            ("after".to_string(), 7, 0),
+        ]
+    );
+    assert_eq!(slots(src, "Book"), 8);
+}
+
+#[test]
+fn fixed_arrays_of_struct_valued_mappings() {
+    let src = "
+        struct Info { uint256 a; uint256 b; uint256 c; }
+        struct S { mapping(uint => Info)[2] infos; uint8 after; }
+    ";
+    // the Info values live behind the hash, not in the array
+    assert_eq!(
+        locations(src, "S"),
+        [("infos".to_string(), 0, 0), ("after".to_string(), 2, 0)]
+    );
+}
+
+#[test]
+fn nested_fixed_arrays_of_structs() {
+    let src = "
+        struct Pair { uint256 a; uint8 b; }
+        struct S { Pair[2][3] grid; uint8 after; }
+    ";
+    assert_eq!(
+        structs(src)["S"].fields[0].1,
+        SolType::FixedArray(
+            Box::new(SolType::FixedArray(
+                Box::new(SolType::Custom2("Pair".to_string())),
+                2
+            )),
+            3
+        )
+    );
+    assert_eq!(
+        locations(src, "S"),
+        [("grid".to_string(), 0, 0), ("after".to_string(), 12, 0)]
+    );
+}
+
+#[test]
+fn address_arrays() {
+    assert_eq!(slots("struct S { address[] owners; }", "S"), 1);
+    // a second address doesn't fit in the 12 bytes left, so each one gets a slot of its own
+    assert_eq!(
+        locations("struct S { address[10] council; address[] owners; }", "S"),
+        [("council".to_string(), 0, 0), ("owners".to_string(), 10, 0)]
+    );
+}
+
+#[test]
+fn structs_of_a_whole_source_file() {
+    let src = include_str!("fixtures/Exchange.sol");
+    let structs = structs(src);
+    let sizes = structs
+        .values()
+        .map(|st| (st.name.as_str(), slot_count(st, &structs).unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sizes,
+        [
+            ("Exchange.Book", 19),
+            ("Exchange.Level", 2),
+            ("IOracle.Quote", 1),
+            ("OrderLib.Order", 2),
+        ]
+    );
+    assert_eq!(
+        locations(src, "OrderLib.Order"),
+        [
+            ("maker".to_string(), 0, 0),
+            ("side".to_string(), 0, 20),
+            ("filled".to_string(), 0, 21),
+            ("price".to_string(), 1, 0),
+            ("amount".to_string(), 1, 16),
+        ]
+    );
+    assert_eq!(
+        locations(src, "Exchange.Book"),
+        [
+            ("base".to_string(), 0, 0),
+            ("quote".to_string(), 1, 0),
+            ("bids".to_string(), 2, 0),
+            ("asks".to_string(), 10, 0),
+            ("balances".to_string(), 18, 0),
+        ]
+    );
+}
+
+#[test]
+fn byte_map_marks_fixed_arrays_by_element() {
+    let structs = structs("struct S { uint8 a; uint16[17] b; uint64 c; }");
+    let slots = layout_report(&structs["S"], &structs)
+        .unwrap()
+        .byte_occupancy()
+        .unwrap();
+    let slot = |bytes: &[(usize, Option<usize>)]| {
+        let mut slot = [None; 32];
+        for &(len, field) in bytes {
+            let start = slot.iter().position(Option::is_none).unwrap();
+            slot[start..start + len].fill(field);
+        }
+        slot
+    };
+    assert_eq!(
+        slots,
+        [
+            slot(&[(1, Some(0))]),
+            slot(&[(32, Some(1))]),
+            slot(&[(2, Some(1))]),
+            slot(&[(8, Some(2))]),
+        ]
+    );
+}
+
+#[test]
+fn byte_map_of_huge_structs() {
+    let structs = structs("struct S { uint256[70000] a; }");
+    let report = layout_report(&structs["S"], &structs).unwrap();
+    assert_eq!(
+        report.byte_occupancy(),
+        Err(LayoutError::TooManySlots(70000))
+    );
+    assert!(report.to_json()["byteMap"].is_null());
+}
+
+#[test]
+fn bool_arrays_pack_a_byte_per_element() {
+    assert_eq!(slots("struct S { bool[] flags; }", "S"), 1);
+    assert_eq!(slots("struct S { bool[32] flags; }", "S"), 1);
+    assert_eq!(slots("struct S { bool[33] flags; }", "S"), 2);
+    assert_eq!(
+        locations("struct S { bool a; bool[31] flags; bool b; }", "S"),
+        [
+            ("a".to_string(), 0, 0),
+            ("flags".to_string(), 1, 0),
+            ("b".to_string(), 2, 0),
+        ]
+    );
+}
+
+#[test]
+fn arrays_of_strings_and_bytes() {
+    let src =
+        "struct S { string[] names; bytes[] blobs; string[3] labels; bytes[2] pair; uint8 b; }";
+    assert_eq!(
+        structs(src)["S"].fields[0].1,
+        SolType::Array(Box::new(SolType::String))
+    );
+    assert_eq!(
+        locations(src, "S"),
+        [
+            ("names".to_string(), 0, 0),
+            ("blobs".to_string(), 1, 0),
+            ("labels".to_string(), 2, 0),
+            ("pair".to_string(), 5, 0),
+            ("b".to_string(), 7, 0),
+        ]
+    );
+}
+
+#[test]
+fn dotted_struct_types() {
+    let src = "
+        library LibAppStorage { struct AppStorage { uint256 supply; uint8 paused; } }
+        struct Facet { LibAppStorage.AppStorage s; mapping(uint => LibAppStorage.AppStorage[]) m; }
+    ";
+    assert_eq!(
+        structs(src)["Facet"].fields[0].1,
+        SolType::Custom2("LibAppStorage.AppStorage".to_string())
+    );
+    assert_eq!(
+        locations(src, "Facet"),
+        [("s".to_string(), 0, 0), ("m".to_string(), 2, 0)]
+    );
+    // with the struct declared at the top level, the library name is only a prefix
+    let flat = "struct AppStorage { uint256 supply; } struct Facet { LibAppStorage.AppStorage s; }";
+    assert_eq!(slots(flat, "Facet"), 1);
+}
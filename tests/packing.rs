@@ -0,0 +1,65 @@
+use proptest::prelude::*;
+use struct_storage_layout::{SolType, compute_layout};
+
+fn value_type() -> impl Strategy<Value = SolType> {
+    prop_oneof![
+        (1..=32u16).prop_map(|bytes| SolType::Uint(bytes * 8)),
+        (1..=32u16).prop_map(|bytes| SolType::Int(bytes * 8)),
+        Just(SolType::Address),
+        Just(SolType::Bool),
+        (1..=32u8).prop_map(SolType::Bytes),
+    ]
+}
+
+/// Bytes a value type takes up, worked out apart from the sizing under test.
+fn bytes(typ: &SolType) -> u64 {
+    match typ {
+        SolType::Uint(bits) | SolType::Int(bits) => u64::from(*bits) / 8,
+        SolType::Address => 20,
+        SolType::Bool => 1,
+        SolType::Bytes(len) => u64::from(*len),
+        typ => unreachable!("{typ} isn't a value type"),
+    }
+}
+
+/// Packs `types` the way solc does: in order, each into the current slot if it still fits and
+/// into the next one otherwise. The slot and offset of every field, and the bytes taken up.
+fn reference_pack(types: &[SolType]) -> (Vec<(u64, u64)>, u64) {
+    let (mut slot, mut offset) = (0, 0);
+    let mut locations = vec![];
+    for typ in types {
+        let len = bytes(typ);
+        if offset + len > 32 {
+            slot += 1;
+            offset = 0;
+        }
+        locations.push((slot, offset));
+        offset += len;
+    }
+
+    (locations, slot * 32 + offset)
+}
+
+proptest! {
+    #[test]
+    fn value_types_pack_like_solc(types in prop::collection::vec(value_type(), 1..24)) {
+        let src = format!(
+            "struct S {{ {} }}",
+            types
+                .iter()
+                .enumerate()
+                .map(|(i, typ)| format!("{typ} f{i};"))
+                .collect::<String>()
+        );
+        let report = &compute_layout(&src).unwrap()[0];
+        let (expected, used) = reference_pack(&types);
+
+        for (field, (slot, offset)) in report.fields.iter().zip(&expected) {
+            // no value type spans a slot boundary
+            prop_assert!(field.offset + field.bits / 8 <= 32, "{} spans a slot", field.name);
+            prop_assert_eq!((field.slot, field.offset), (*slot, *offset), "{}", field.name);
+        }
+        prop_assert_eq!(report.total_bits, used * 8);
+        prop_assert_eq!(report.total_slots, used.div_ceil(32));
+    }
+}
@@ -0,0 +1,435 @@
+use serde_json::{Value, json};
+use struct_storage_layout::{
+    LayoutError, SolType, ast::parse_ast, compute_layout, parse_structs, parse_structs_strict,
+};
+
+/// The name and type of every field of `name`.
+fn fields(src: &str, name: &str) -> Vec<(String, String)> {
+    parse_structs(src).unwrap()[name]
+        .fields
+        .iter()
+        .map(|(name, typ, _)| (name.clone(), typ.to_string()))
+        .collect()
+}
+
+fn names(src: &str, name: &str) -> Vec<String> {
+    fields(src, name)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[test]
+fn braces_in_comments_and_strings_dont_close_the_struct() {
+    let src = r#"
+        struct S {
+            uint256 a; // closes here }
+            /* } */ uint256 b;
+            uint256 c;
+        }
+        string constant CLOSE = "}";
+        struct T { uint8 d; }
+    "#;
+    assert_eq!(names(src, "S"), ["a", "b", "c"]);
+    assert_eq!(names(src, "T"), ["d"]);
+}
+
+#[test]
+fn struct_inside_a_contract() {
+    let src = "
+        struct Top { uint256 x; }
+        contract Vault {
+            uint256 total;
+            struct Order {
+                address owner;
+                uint96 price;
+            }
+            function f() public { if (true) { total = 1; } }
+        }
+    ";
+    let structs = parse_structs(src).unwrap();
+    assert_eq!(structs.keys().collect::<Vec<_>>(), ["Top", "Vault.Order"]);
+    assert_eq!(names(src, "Vault.Order"), ["owner", "price"]);
+}
+
+#[test]
+fn compact_declarations() {
+    let pair = ["a".to_string(), "b".to_string()];
+    assert_eq!(names("struct S { uint256 a; uint256 b; }", "S"), pair);
+    assert_eq!(names("struct S {\n  uint256 a; uint256 b; }", "S"), pair);
+    assert_eq!(
+        names("struct S {\n  uint256 a;\n  uint256 b; }\n", "S"),
+        pair
+    );
+    assert_eq!(names("struct S\n{\n  uint256 a; uint256 b;\n}", "S"), pair);
+}
+
+#[test]
+fn leftovers_arent_taken_for_struct_names() {
+    assert!(matches!(
+        parse_structs("struct S { uint+ a; }"),
+        Err(LayoutError::InvalidType(_))
+    ));
+    assert!(matches!(
+        parse_structs("struct S { uint256 a; ) b; }"),
+        Err(LayoutError::InvalidType(_))
+    ));
+}
+
+#[test]
+fn invalid_mapping_keys() {
+    for (key, kind) in [
+        ("uint256[]", "array"),
+        ("uint256[2]", "array"),
+        ("mapping(uint256 => uint256)", "mapping"),
+    ] {
+        let err = format!("mapping({key} => uint256)")
+            .parse::<SolType>()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            LayoutError::InvalidMappingKey {
+                kind,
+                key: key.to_string()
+            }
+        );
+    }
+
+    let err = compute_layout("struct K { uint256 a; } struct S { mapping(K => uint256) m; }")
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("mappings cannot be keyed by struct 'K'"),
+        "{err}"
+    );
+}
+
+#[test]
+fn address_payable_is_an_address() {
+    let src = "struct S { address a; address payable b; uint8 c; }";
+    assert_eq!(
+        fields(src, "S"),
+        [
+            ("a".to_string(), "address".to_string()),
+            ("b".to_string(), "address".to_string()),
+            ("c".to_string(), "uint8".to_string()),
+        ]
+    );
+    let reports = compute_layout(src).unwrap();
+    assert_eq!(reports[0].total_slots, 2);
+}
+
+#[test]
+fn two_declarations_on_one_line() {
+    let src = "struct S {\n  uint128 a; uint128 b;\n}";
+    assert_eq!(names(src, "S"), ["a", "b"]);
+    assert_eq!(compute_layout(src).unwrap()[0].total_slots, 1);
+}
+
+#[test]
+fn crlf_and_byte_order_mark() {
+    let src = "\u{feff}struct S {\r\n    uint128 a;\r\n    address payable b;\r\n}\r\n";
+    assert_eq!(
+        fields(src, "S"),
+        [
+            ("a".to_string(), "uint128".to_string()),
+            ("b".to_string(), "address".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn library_structs() {
+    let src = "
+        library LibAppStorage {
+            struct AppStorage {
+                uint256 supply;
+                mapping(address => uint256) balances;
+            }
+            function appStorage() internal pure returns (AppStorage storage s) {
+                assembly { s.slot := 0 }
+            }
+        }
+    ";
+    assert_eq!(
+        names(src, "LibAppStorage.AppStorage"),
+        ["supply", "balances"]
+    );
+}
+
+#[test]
+fn struct_headers_anywhere_on_a_line() {
+    let src = "library L { struct A { uint a; } } struct B { uint8 b; } struct C {\nuint c; }";
+    let structs = parse_structs(src).unwrap();
+    assert_eq!(structs.keys().collect::<Vec<_>>(), ["B", "C", "L.A"]);
+    assert_eq!(names(src, "B"), ["b"]);
+}
+
+#[test]
+fn stray_semicolons_and_commas() {
+    let src = "struct S {\n  uint256 a;;\n  ;uint256 b;\n  ;\n  uint8 c;,\n  , uint8 d;\n}";
+    assert_eq!(names(src, "S"), ["a", "b", "c", "d"]);
+
+    // a type without a name is still an error
+    assert!(matches!(
+        parse_structs("struct S {\n  uint256;\n}"),
+        Err(LayoutError::ParseError { line: 2, .. })
+    ));
+}
+
+#[test]
+fn data_locations_are_stripped() {
+    let src = "
+        struct MyStruct { uint256 a; }
+        struct S { MyStruct storage s; bytes memory b; string calldata c; }
+    ";
+    assert_eq!(
+        fields(src, "S"),
+        [
+            ("s".to_string(), "MyStruct".to_string()),
+            ("b".to_string(), "bytes".to_string()),
+            ("c".to_string(), "string".to_string()),
+        ]
+    );
+    let reports = compute_layout(src).unwrap();
+    let s = reports.iter().find(|report| report.struct_name == "S");
+    assert_eq!(s.unwrap().total_slots, 3);
+}
+
+#[test]
+fn strict_mode_rejects_unknown_type_names() {
+    let src = "
+        struct S {
+            uint256 a;
+            unit256 b;
+        }
+    ";
+    assert!(parse_structs(src).is_ok());
+    assert_eq!(
+        parse_structs_strict(src),
+        Err(LayoutError::ParseError {
+            line: 4,
+            msg: "unknown type unit256 of field b".to_string(),
+        })
+    );
+
+    let known = "
+        type Price is uint128;
+        enum Side { Buy, Sell }
+        interface IERC20 {}
+        library Lib { struct Entry { uint256 x; } }
+        struct S { Price p; Side s; IERC20 t; Lib.Entry e; mapping(address => Entry[]) m; }
+    ";
+    assert!(parse_structs_strict(known).is_ok());
+}
+
+#[test]
+fn constant_expressions_in_array_lengths() {
+    let src = "
+        uint256 constant N = 4;
+        uint256 constant M = N + 1;
+        struct S { uint256[N * 2] a; uint8[(M - 1) * (N + 1)] b; }
+    ";
+    assert_eq!(
+        fields(src, "S"),
+        [
+            ("a".to_string(), "uint256[8]".to_string()),
+            ("b".to_string(), "uint8[20]".to_string()),
+        ]
+    );
+
+    let constants = [("N".to_string(), 4)].into();
+    for (typ, msg) in [
+        ("uint256[N * K]", "K isn't a known constant"),
+        ("uint256[(N + 1]", "unbalanced parentheses"),
+        ("uint256[1 - N]", "goes negative"),
+        ("uint256[N / 2]", "unexpected /"),
+    ] {
+        let Err(LayoutError::InvalidType(e)) = SolType::parse_with_constants(typ, &constants)
+        else {
+            panic!("{typ} parsed");
+        };
+        assert!(e.ends_with(msg), "{typ}: {e}");
+    }
+}
+
+#[test]
+fn bare_uint_and_int_print_their_width() {
+    let src = "struct S { uint a; int b; mapping(uint => int[]) m; }";
+    let expected = ["uint256", "int256", "mapping(uint256 => int256[])"];
+    let printed = fields(src, "S")
+        .into_iter()
+        .map(|(_, typ)| typ)
+        .collect::<Vec<_>>();
+    assert_eq!(printed, expected);
+
+    let layout = compute_layout(src).unwrap()[0].to_json();
+    let types = layout["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["type"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(types, expected);
+}
+
+#[test]
+fn struct_names_are_scoped_by_their_contract() {
+    // both scopes open and close on the line of a struct header
+    let src = "
+        contract A { struct Data { uint8 x; } }
+        contract B { struct Data { uint256 y; } struct User { Data d; A.Data a; } }
+        struct Top { B.Data b; }
+    ";
+    assert_eq!(
+        fields(src, "B.User"),
+        [
+            ("d".to_string(), "B.Data".to_string()),
+            ("a".to_string(), "A.Data".to_string()),
+        ]
+    );
+    assert_eq!(
+        fields(src, "Top"),
+        [("b".to_string(), "B.Data".to_string())]
+    );
+
+    let ambiguous = format!("{src} struct Loose {{ Data d; }}");
+    let Err(LayoutError::Field { owner, source, .. }) = parse_structs(&ambiguous) else {
+        panic!("a bare Data outside of both contracts resolved");
+    };
+    assert_eq!(owner, "Loose");
+    assert_eq!(
+        *source,
+        LayoutError::AmbiguousStruct {
+            name: "Data".to_string(),
+            candidates: vec!["A.Data".to_string(), "B.Data".to_string()],
+        }
+    );
+}
+
+#[test]
+fn ast_structs_are_named_by_their_canonical_name() {
+    let uint = |id, bits| {
+        json!({
+            "id": id,
+            "nodeType": "ElementaryTypeName",
+            "name": format!("uint{bits}"),
+        })
+    };
+    let definition = |id, name, canonical: &str, members: Value| {
+        json!({
+            "id": id,
+            "nodeType": "StructDefinition",
+            "name": name,
+            "canonicalName": canonical,
+            "members": members,
+        })
+    };
+    let member = |id, name, type_name| {
+        json!({
+            "id": id,
+            "nodeType": "VariableDeclaration",
+            "name": name,
+            "typeName": type_name,
+        })
+    };
+    let data = json!({"id": 9, "nodeType": "UserDefinedTypeName", "referencedDeclaration": 5});
+    let src = json!({
+        "id": 1,
+        "nodeType": "SourceUnit",
+        "nodes": [
+            {
+                "id": 2,
+                "nodeType": "ContractDefinition",
+                "name": "A",
+                "nodes": [definition(4, "Data", "A.Data", json!([member(10, "x", uint(11, 8))]))],
+            },
+            {
+                "id": 3,
+                "nodeType": "ContractDefinition",
+                "name": "B",
+                "nodes": [
+                    definition(5, "Data", "B.Data", json!([member(12, "y", uint(13, 256))])),
+                    definition(6, "User", "B.User", json!([member(14, "d", data)])),
+                ],
+            },
+        ],
+    });
+    let structs = parse_ast(&src.to_string()).unwrap();
+    assert_eq!(
+        structs.keys().collect::<Vec<_>>(),
+        ["A.Data", "B.Data", "B.User"]
+    );
+    assert_eq!(
+        structs["B.User"].fields[0].1,
+        SolType::Custom2("B.Data".to_string())
+    );
+}
+
+#[test]
+fn zero_length_arrays_are_rejected() {
+    assert_eq!(
+        "uint256[0]".parse::<SolType>(),
+        Err(LayoutError::InvalidType(
+            "invalid array size: 0 in uint256[0]".to_string()
+        ))
+    );
+    let constants = [("N".to_string(), 2)].into();
+    assert!(SolType::parse_with_constants("uint8[N - 2][3]", &constants).is_err());
+    assert!(parse_structs("struct S { uint256[0] x; }").is_err());
+}
+
+#[test]
+fn declarations_wrapped_across_lines() {
+    let src = "
+        struct Entry { uint256 x; }
+        struct S {
+            mapping(address =>
+                mapping(uint256 => Entry)) data;
+            uint128
+                total;
+        }
+    ";
+    assert_eq!(
+        fields(src, "S"),
+        [
+            (
+                "data".to_string(),
+                "mapping(address => mapping(uint256 => Entry))".to_string()
+            ),
+            ("total".to_string(), "uint128".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn structs_without_a_header_or_a_body() {
+    for (src, line, msg) in [
+        ("struct Foo", 1, "struct Foo has no body"),
+        (
+            "struct Foo\nuint a;",
+            2,
+            "expected { after struct Foo, found: uint a;",
+        ),
+        (
+            "\nstruct Foo {\n    uint a;",
+            2,
+            "struct Foo is missing its closing brace",
+        ),
+        ("struct Foo;", 1, "invalid struct header: struct Foo;"),
+        (
+            "contract C {\n    struct { uint a; }\n}",
+            2,
+            "struct without a name: struct { uint a; }",
+        ),
+    ] {
+        assert_eq!(
+            parse_structs(src),
+            Err(LayoutError::ParseError {
+                line,
+                msg: msg.to_string()
+            }),
+            "{src}"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use struct_storage_layout::{
+    LayoutError, LayoutReport, compute_layout,
+    contract::parse_contracts,
+    layout_report, layout_reports, parse_structs,
+    slot::{find_field, slot_of},
+};
+
+fn hex(word: [u8; 32]) -> String {
+    word.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn contract_reports(src: &str) -> Vec<LayoutReport> {
+    let structs = parse_structs(src).unwrap();
+    parse_contracts(src)
+        .unwrap()
+        .values()
+        .map(|contract| layout_report(contract, &structs).unwrap())
+        .collect()
+}
+
+#[test]
+fn mapping_entry_slot() {
+    let reports = contract_reports("contract V { mapping(uint256 => uint256) m; }");
+    // keccak256(abi.encode(uint256(0), uint256(0)))
+    assert_eq!(
+        hex(slot_of("m[0]", &reports).unwrap()),
+        "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+    );
+}
+
+#[test]
+fn invalid_keys() {
+    let reports = contract_reports("contract V { mapping(address => mapping(uint8 => bool)) m; }");
+    assert!(slot_of("m[0x12]", &reports).is_ok());
+    assert!(matches!(
+        slot_of("m[12]", &reports),
+        Err(LayoutError::InvalidKey(_))
+    ));
+    assert!(matches!(
+        slot_of("m[0x12][256]", &reports),
+        Err(LayoutError::InvalidKey(_))
+    ));
+    assert!(matches!(
+        slot_of("m[0x12][1][2]", &reports),
+        Err(LayoutError::InvalidSlotExpr(_))
+    ));
+    assert!(matches!(
+        slot_of("m", &reports),
+        Err(LayoutError::InvalidSlotExpr(_))
+    ));
+}
+
+#[test]
+fn unknown_and_ambiguous_fields() {
+    let reports = compute_layout("struct S { uint a; } struct T { uint a; }").unwrap();
+    assert!(matches!(
+        find_field("b", &reports),
+        Err(LayoutError::UnknownField(_))
+    ));
+    assert!(matches!(
+        find_field("a", &reports),
+        Err(LayoutError::AmbiguousField { .. })
+    ));
+    assert_eq!(find_field("T.a", &reports).unwrap().slot, 0);
+    let structs = parse_structs("struct S { uint a; uint b; }").unwrap();
+    assert_eq!(
+        find_field("b", &layout_reports(&structs).unwrap())
+            .unwrap()
+            .slot,
+        1
+    );
+}
@@ -0,0 +1,48 @@
+//! Snapshots of the printed output, so changes to its format show up in review. Run
+//! `cargo insta review` after an intended change.
+
+use std::process::Command;
+
+const SRC: &str = "
+    struct Inner {
+        uint64 a;
+        bool b;
+    }
+
+    struct Position {
+        /// Who holds it.
+        address owner;
+        uint64 openedAt;
+        bool active;
+        mapping(uint256 => uint256) fills;
+        uint32[5] history;
+        Inner inner;
+        uint128 size;
+    }
+";
+
+fn output(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_struct-storage-layout"))
+        .arg("--inline")
+        .arg(SRC)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn text() {
+    insta::assert_snapshot!(output(&[]));
+}
+
+#[test]
+fn json() {
+    insta::assert_snapshot!(output(&["--json"]));
+}
+
+#[test]
+fn diagram() {
+    insta::assert_snapshot!(output(&["--format", "diagram"]));
+}
@@ -0,0 +1,36 @@
+use struct_storage_layout::{parse_structs, sstore_count};
+
+fn sstores(src: &str, name: &str) -> u64 {
+    let structs = parse_structs(src).unwrap();
+    sstore_count(&structs[name], &structs).unwrap()
+}
+
+#[test]
+fn packed_values_share_a_write() {
+    assert_eq!(
+        sstores("struct S { uint128 a; uint128 b; uint256 c; }", "S"),
+        2
+    );
+}
+
+#[test]
+fn mapping_entries_and_array_lengths() {
+    let src = "struct S { mapping(uint256 => uint256) m; uint256[] xs; }";
+    assert_eq!(sstores(src, "S"), 3);
+}
+
+#[test]
+fn recursive_structs_count_the_entry_once() {
+    let src = "struct Node { uint256 value; mapping(uint256 => Node) children; Node[] kids; }";
+    assert_eq!(sstores(src, "Node"), 4);
+}
+
+#[test]
+fn mutually_recursive_structs() {
+    let src = "
+        struct A { uint256 x; mapping(uint256 => B) bs; }
+        struct B { uint256 y; A[] parents; }
+    ";
+    // x, then B's y, then the length of parents and an A that's already being written
+    assert_eq!(sstores(src, "A"), 4);
+}
@@ -0,0 +1,84 @@
+use proptest::prelude::*;
+use struct_storage_layout::SolType;
+
+fn value_type() -> impl Strategy<Value = SolType> {
+    prop_oneof![
+        (1..=32u16).prop_map(|bytes| SolType::Uint(bytes * 8)),
+        (1..=32u16).prop_map(|bytes| SolType::Int(bytes * 8)),
+        Just(SolType::Address),
+        Just(SolType::Bool),
+        (1..=32u8).prop_map(SolType::Bytes),
+    ]
+}
+
+/// Anything a mapping can be keyed by.
+fn key_type() -> impl Strategy<Value = SolType> {
+    prop_oneof![
+        value_type(),
+        Just(SolType::BytesArbitrary),
+        Just(SolType::String),
+        "[A-Z][a-zA-Z0-9_]{0,8}".prop_map(SolType::Custom2),
+    ]
+}
+
+fn sol_type() -> impl Strategy<Value = SolType> {
+    key_type().prop_recursive(4, 16, 2, |inner| {
+        prop_oneof![
+            (key_type(), inner.clone())
+                .prop_map(|(key, value)| SolType::Mapping(Box::new(key), Box::new(value))),
+            inner.clone().prop_map(|typ| SolType::Array(Box::new(typ))),
+            (inner, 1..1_000u64).prop_map(|(typ, len)| SolType::FixedArray(Box::new(typ), len)),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn display_round_trips(typ in sol_type()) {
+        prop_assert_eq!(typ.to_string().parse::<SolType>().unwrap(), typ);
+    }
+}
+
+#[test]
+fn aliases_display_canonically() {
+    for (alias, canonical) in [
+        ("uint", "uint256"),
+        ("int", "int256"),
+        ("address payable", "address"),
+        ("mapping(uint => uint[])", "mapping(uint256 => uint256[])"),
+    ] {
+        assert_eq!(alias.parse::<SolType>().unwrap().to_string(), canonical);
+    }
+}
+
+#[test]
+fn value_types_and_types_forcing_a_new_slot() {
+    let src = "
+        enum Side { Buy, Sell }
+        type Price is uint128;
+        interface IERC20 {}
+        struct Inner { uint8 x; }
+        struct S {
+            uint8 a; int256 b; address c; bool d; bytes4 e; Side f; Price g; IERC20 h;
+            bytes i; string j; mapping(uint => uint) k; uint[] l; uint8[2] m; Inner n;
+        }
+    ";
+    let structs = struct_storage_layout::parse_structs(src).unwrap();
+    let (values, anchored): (Vec<_>, Vec<_>) = structs["S"]
+        .fields
+        .iter()
+        .map(|(name, typ, _)| (name.as_str(), typ))
+        .partition(|(_, typ)| typ.is_value_type());
+    assert_eq!(
+        values.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+        ["a", "b", "c", "d", "e", "f", "g", "h"]
+    );
+    assert_eq!(
+        anchored.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+        ["i", "j", "k", "l", "m", "n"]
+    );
+    for (name, typ) in values.iter().chain(&anchored) {
+        assert_eq!(typ.forces_new_slot(), !typ.is_value_type(), "{name}");
+    }
+    assert!(SolType::Custom(structs["Inner"].clone()).forces_new_slot());
+}